@@ -5,5 +5,6 @@ pub use menu::*;
 mod chapter_markers;
 pub use chapter_markers::*;
 pub mod chapter1;
+pub mod chapter2;
 pub mod code_editor;
 pub mod landing_page;
@@ -1,12 +1,15 @@
 use crate::dark_magic::BoxedAnything;
+use parallel_tempering::{ParallelTemperingData, ParallelTemperingParameters};
+use wang_landau::{WangLandauConfig, WangLandauParameters};
 
 use super::Chapter1;
 
-mod coin_sequence_wl;
+pub mod coin_sequence_wl;
 mod first;
-mod parallel_tempering;
+pub mod parallel_tempering;
+mod rate_function;
 mod second;
-mod wang_landau;
+pub mod wang_landau;
 
 pub fn chapter_1_switch(which: &Chapter1, any: &mut BoxedAnything, ctx: &egui::Context) {
     match which {
@@ -20,3 +23,83 @@ pub fn chapter_1_switch(which: &Chapter1, any: &mut BoxedAnything, ctx: &egui::C
         }
     }
 }
+
+/// Stores the parameters of the page under `key`, if the page has any worth remembering
+pub fn save_parameters(
+    which: &Chapter1,
+    any: &BoxedAnything,
+    storage: &mut dyn eframe::Storage,
+    key: &str,
+) {
+    match which {
+        Chapter1::Third => {
+            if let Some(data) = any.to_something::<ParallelTemperingData>() {
+                eframe::set_value(storage, key, &data.parameters());
+            }
+        }
+        Chapter1::WangLandau => {
+            if let Some(data) = any.to_something::<WangLandauConfig>() {
+                eframe::set_value(storage, key, &data.parameters());
+            }
+        }
+        Chapter1::First | Chapter1::Second => (),
+    }
+}
+
+/// Restores the parameters stored by [save_parameters]
+pub fn load_parameters(
+    which: &Chapter1,
+    any: &mut BoxedAnything,
+    storage: &dyn eframe::Storage,
+    key: &str,
+) {
+    match which {
+        Chapter1::Third => {
+            if let Some(parameters) = eframe::get_value::<ParallelTemperingParameters>(storage, key)
+            {
+                let data: &mut ParallelTemperingData = any.to_something_or_default_mut();
+                data.apply_parameters(parameters);
+            }
+        }
+        Chapter1::WangLandau => {
+            if let Some(parameters) = eframe::get_value::<WangLandauParameters>(storage, key) {
+                let data: &mut WangLandauConfig = any.to_something_or_default_mut();
+                data.apply_parameters(parameters);
+            }
+        }
+        Chapter1::First | Chapter1::Second => (),
+    }
+}
+
+/// Query for a link to the page with its current parameters, see [crate::app::split_link]
+pub fn link_query(which: &Chapter1, any: &BoxedAnything) -> Option<String> {
+    match which {
+        Chapter1::Third => any
+            .to_something::<ParallelTemperingData>()
+            .map(|data| data.parameters().to_query()),
+        Chapter1::WangLandau => any
+            .to_something::<WangLandauConfig>()
+            .map(|data| data.parameters().to_query()),
+        Chapter1::First | Chapter1::Second => None,
+    }
+}
+
+/// Sets the page up as described by the query of a link and starts the simulation
+pub fn follow_link_query(which: &Chapter1, any: &mut BoxedAnything, query: &[(&str, &str)]) {
+    match which {
+        Chapter1::Third => {
+            let data: &mut ParallelTemperingData = any.to_something_or_default_mut();
+            let mut parameters = data.parameters();
+            parameters.update_from_query(query);
+            data.apply_parameters(parameters);
+        }
+        Chapter1::WangLandau => {
+            let data: &mut WangLandauConfig = any.to_something_or_default_mut();
+            let mut parameters = data.parameters();
+            parameters.update_from_query(query);
+            data.apply_parameters(parameters);
+            data.start_simulation();
+        }
+        Chapter1::First | Chapter1::Second => (),
+    }
+}
@@ -1,5 +1,67 @@
 use rand::Rng;
 use sampling::{HasRng, MarkovChain};
+use serde::{Deserialize, Serialize};
+
+/// # Probability that a coin shows head
+///
+/// Strictly between 0 and 1, such that every sequence is possible
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(try_from = "f64", into = "f64")]
+pub struct HeadsProbability(f64);
+
+impl HeadsProbability {
+    /// Smallest probability that can be chosen, the largest one is 1 - MIN
+    pub const MIN: f64 = 0.01;
+
+    /// None if `p` is outside of `MIN..=1-MIN`
+    pub fn new(p: f64) -> Option<Self> {
+        (Self::MIN..=1.0 - Self::MIN)
+            .contains(&p)
+            .then_some(Self(p))
+    }
+
+    pub fn get(self) -> f64 {
+        self.0
+    }
+
+    pub fn is_fair(self) -> bool {
+        self.0 == 0.5
+    }
+
+    /// Probability of the smaller of head and tail
+    pub fn min_side(self) -> f64 {
+        self.0.min(1.0 - self.0)
+    }
+
+    /// Throws a coin
+    pub fn throw<R: Rng>(self, rng: &mut R) -> CoinFlip {
+        if rng.random_bool(self.0) {
+            CoinFlip::Head
+        } else {
+            CoinFlip::Tail
+        }
+    }
+}
+
+impl Default for HeadsProbability {
+    fn default() -> Self {
+        Self(0.5)
+    }
+}
+
+impl TryFrom<f64> for HeadsProbability {
+    type Error = String;
+
+    fn try_from(p: f64) -> Result<Self, Self::Error> {
+        Self::new(p).ok_or_else(|| format!("heads probability {p} is not allowed"))
+    }
+}
+
+impl From<HeadsProbability> for f64 {
+    fn from(p: HeadsProbability) -> Self {
+        p.0
+    }
+}
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 /// # Result of flipping a coin
@@ -27,11 +89,24 @@ pub struct CoinFlipMove {
     index: usize,
 }
 
+impl CoinFlipMove {
+    /// Result of the flipped coin before the step
+    pub fn previous(&self) -> CoinFlip {
+        self.previous
+    }
+
+    /// Position of the flipped coin
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
 #[derive(Clone, Debug)]
 /// # A sequence of Coin flips. Contains random Number generator
 pub struct CoinFlipSequence<R> {
     rng: R,
     seq: Vec<CoinFlip>,
+    heads_probability: HeadsProbability,
     /// You can ignore everything after here, it is just used for testing
     steps: usize,
     rejected: usize,
@@ -45,19 +120,15 @@ where
 {
     /// Create new coin flip sequence
     /// * length `n`
+    /// * every coin shows head with probability `heads_probability`
     /// * use `rng` as random number generator
-    pub fn new(n: usize, mut rng: R) -> Self {
+    pub fn new(n: usize, heads_probability: HeadsProbability, mut rng: R) -> Self {
         let mut seq = Vec::with_capacity(n);
-        seq.extend((0..n).map(|_| {
-            if rng.random::<bool>() {
-                CoinFlip::Tail
-            } else {
-                CoinFlip::Head
-            }
-        }));
+        seq.extend((0..n).map(|_| heads_probability.throw(&mut rng)));
         Self {
             rng,
             seq,
+            heads_probability,
             steps: 0,
             rejected: 0,
             accepted: 0,
@@ -67,6 +138,16 @@ where
 }
 
 impl<R> CoinFlipSequence<R> {
+    /// The coin flips of the sequence
+    pub fn flips(&self) -> &[CoinFlip] {
+        &self.seq
+    }
+
+    /// Biased coins are not always flipped by a markov step
+    pub fn is_unchanged_by(&self, step: &CoinFlipMove) -> bool {
+        self.seq[step.index] == step.previous
+    }
+
     /// Count how often `Head` occurs in the Coin flip sequence
     pub fn head_count(&self) -> u32 {
         self.seq
@@ -81,6 +162,9 @@ impl<R> CoinFlipSequence<R> {
     /// * might **panic** if `step` was not the markov step leading from the ensemble with `head_count`
     ///   to the current ensemble - if it does not panic, the result will be wrong
     pub fn update_head_count(&self, step: &CoinFlipMove, head_count: &mut u32) {
+        if self.is_unchanged_by(step) {
+            return;
+        }
         match step.previous {
             CoinFlip::Head => {
                 *head_count -= 1;
@@ -101,8 +185,16 @@ where
         // draw a random position
         let pos = self.rng.random_range(0..self.seq.len());
         let previous = self.seq[pos];
-        // flip coin at that position
-        self.seq[pos].turn();
+        // flip coin at that position, with the Metropolis probability of the bias.
+        // Fair coins are always flipped
+        let p = self.heads_probability.get();
+        let (p_old, p_new) = match previous {
+            CoinFlip::Head => (p, 1.0 - p),
+            CoinFlip::Tail => (1.0 - p, p),
+        };
+        if p_new >= p_old || self.rng.random::<f64>() * p_old < p_new {
+            self.seq[pos].turn();
+        }
         // information to restore the previouse state
         CoinFlipMove {
             previous,
@@ -1,5 +1,7 @@
-use super::wang_landau::calc_true_log;
-use crate::dark_magic::BoxedAnything;
+use super::coin_sequence_wl::HeadsProbability;
+use super::rate_function::{binomial_rate_points, log10_slice_to_rate};
+use super::wang_landau::{calc_true_log, heads_probability_slider, Speed};
+use crate::dark_magic::{pick_file, save_file, BoxedAnything, PendingFile, Scheduler};
 use crate::misc::*;
 use core::f64;
 use derivative::Derivative;
@@ -8,20 +10,32 @@ use egui_plot::{
     AxisHints, Bar, BarChart, Legend, Line, MarkerShape, Plot, PlotBounds, PlotPoint, PlotPoints,
     Points, Text,
 };
-use num_traits::Signed;
-use ordered_float::NotNan;
-use rand::{
-    distr::Uniform,
-    prelude::{Distribution, IndexedMutRandom},
-    Rng, SeedableRng,
-};
-use rand_pcg::Pcg64;
-use sampling::{HistI32Fast, Histogram};
-use std::{
-    collections::{BTreeMap, BTreeSet},
-    mem::swap,
-    num::{NonZeroI32, NonZeroU32, NonZeroUsize},
-};
+use sampling::Histogram;
+use serde::{Deserialize, Serialize};
+use std::num::{NonZeroU32, NonZeroUsize};
+
+mod autocorrelation;
+pub use autocorrelation::*;
+mod engine;
+pub use engine::*;
+mod equilibration;
+pub use equilibration::*;
+mod exchange;
+pub use exchange::*;
+mod export;
+pub use export::*;
+mod independent;
+pub use independent::*;
+mod ladder;
+pub use ladder::*;
+mod model;
+pub use model::*;
+mod replica;
+pub use replica::*;
+mod session;
+pub use session::*;
+mod wham;
+pub use wham::*;
 
 const COLORS: [DarkLightColor; 11] = [
     DarkLightColor {
@@ -75,290 +89,283 @@ const DEFAULT_TEMPERATURES: [f64; 8] = [0.1, 0.025, 0.005, 0.0075, -0.1, -0.01,
 const MONOSPACE_LEN: usize = 18;
 
 const DRAG_SPEED: f64 = 0.01;
+/// Largest number of elements of every configuration
+const MAX_SIZE: u32 = 10000;
 
 #[derive(Derivative)]
 #[derivative(Default)]
 pub struct ParallelTemperingData {
-    #[derivative(Default(value = "Vec::new()"))]
-    temperatures: Vec<Temperature>,
+    /// Contains the temperatures and does the actual sampling
+    engine: ParallelTempering<AnyModel>,
     /// If user clicks on add temperature, this one is added
     #[derivative(Default(value = "-0.5"))]
     temperature_to_add: f64,
-    #[derivative(Default(value = "NonZeroU32::new(100).unwrap()"))]
-    num_coins: NonZeroU32,
-    #[derivative(Default(value = "Pcg64::seed_from_u64(832147)"))]
-    rng: Pcg64,
     paused: bool,
     step_once: bool,
+    /// One step per frame is the default, such that the history can be followed
+    speed: Speed,
+    #[derivative(Default(value = "NonZeroUsize::new(1).unwrap()"))]
+    slow_motion_speed: NonZeroUsize,
     marker_cycle: Option<Box<dyn Iterator<Item = MarkerShape>>>,
-    step_counter: u32,
     color_cycle: Option<Box<dyn Iterator<Item = u8>>>,
     side_panel: SidePanelView,
     #[derivative(Default(value = "Show::Yes"))]
     show_plot: Show,
     show_histogram: Show,
     show_acceptance: Show,
+    show_autocorrelation: Show,
     show_history: Show,
     show_exchange_rate: Show,
     show_estimate: Show,
+    show_replica_flow: Show,
     #[derivative(Default(value = "Show::Yes"))]
     z_legend: Show,
-    #[derivative(Default(value = "Box::new(0..)"))]
-    id_iter: Box<dyn Iterator<Item = u16>>,
-    pair_acceptance: PairAcceptance,
+    /// Shows the merged estimate as large deviation rate function instead of log10 probability
+    rate_function: Show,
     help: Show,
     z: Vec<f64>,
     show_z: Show,
-    #[derivative(Default(value = "calc_true_log(NonZeroU32::new(100).unwrap())"))]
-    true_density: Vec<f64>,
+    merge_method: MergeMethod,
+    wham: Wham,
+    /// Index of the temperature whose z stays fixed during the automatic alignment
+    z_reference: usize,
+    /// Shares the frame time between the engine and the independent runs
+    scheduler: Scheduler,
+    /// Used for the error bars of the merged estimate
+    independent_runs: Option<IndependentRuns<AnyModel>>,
+    #[derivative(Default(value = "8"))]
+    num_independent_runs: usize,
+    /// Session file the user is choosing
+    pending_load: Option<PendingFile<Option<Vec<u8>>>>,
+    /// Session file that is being saved
+    pending_save: Option<PendingFile<Option<std::io::Result<()>>>>,
+    /// Result of the last save or load
+    session_message: Option<String>,
+    export_separator: Separator,
+    /// Exported table that is being saved
+    pending_export: Option<PendingFile<Option<std::io::Result<()>>>>,
+    /// Result of the last export
+    export_message: Option<String>,
+    /// None if the model has no analytic solution
+    #[derivative(Default(
+        value = "Some(calc_true_log(NonZeroU32::new(100).unwrap(), Default::default()))"
+    ))]
+    true_density: Option<Vec<f64>>,
 }
 
-impl ParallelTemperingData {
-    fn add_temperature(&mut self, to_add: f64) -> bool {
-        if !self.contains_temp(to_add) {
-            let num_coins = NonZeroI32::new(self.num_coins.get() as i32).unwrap();
-            self.temperatures.push(Temperature::new(
-                to_add,
-                num_coins,
-                &mut self.rng,
-                self.marker_cycle.as_mut().unwrap().next().unwrap(),
-                self.color_cycle.as_mut().unwrap().next().unwrap(),
-                self.id_iter.next().unwrap(),
-            ));
-            true
-        } else {
-            false
-        }
-    }
-
-    fn remove(&mut self, to_remove: ToRemove) {
-        match to_remove {
-            ToRemove::Nothing => return,
-            ToRemove::Top => {
-                self.temperatures.pop();
-            }
-            ToRemove::Bottom => {
-                self.temperatures.remove(0);
-            }
-            ToRemove::Idx(idx) => {
-                self.temperatures.remove(idx);
-            }
-        }
-        self.pair_acceptance.update_pairs(&self.temperatures);
-    }
-
-    fn new_length(&mut self) {
-        self.temperatures.iter_mut().for_each(|temp| {
-            let len = NonZeroI32::new(self.num_coins.get() as i32).unwrap();
-            temp.adjust_length(len, &mut self.rng);
-        });
-        self.pair_acceptance.reset_counts();
-        self.true_density = calc_true_log(self.num_coins);
-    }
-
-    fn count_shown_plots(&self) -> u8 {
-        self.show_plot.to_num()
-            + self.show_acceptance.to_num()
-            + self.show_histogram.to_num()
-            + self.show_history.to_num()
-            + self.show_exchange_rate.to_num()
-            + self.show_estimate.to_num()
-    }
+/// The parameters of the page that are worth remembering
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParallelTemperingParameters {
+    pub model: ModelKind,
+    /// Only used by [ModelKind::Coins]
+    #[serde(default)]
+    pub heads_probability: HeadsProbability,
+    pub size: NonZeroU32,
+    pub temperatures: Vec<f64>,
+    pub exchange_strategy: ExchangeStrategy,
 }
 
-#[derive(Debug, Default)]
-pub enum SidePanelView {
-    Shown,
-    Hidden,
-    #[default]
-    Default,
-}
-
-impl ParallelTemperingData {
-    pub fn sort_temps(&mut self) {
-        self.temperatures.sort_by_cached_key(|a| SortHelper {
-            temp: NotNan::new(a.temperature).unwrap(),
-        });
-        self.pair_acceptance.update_pairs(&self.temperatures);
+impl ParallelTemperingParameters {
+    /// Encodes the parameters for a link, see [crate::app::split_link]
+    pub fn to_query(&self) -> String {
+        let model = match self.model {
+            ModelKind::Coins => "coins",
+            ModelKind::IsingChain => "ising",
+        };
+        let exchange = match self.exchange_strategy {
+            ExchangeStrategy::RandomPairs => "random",
+            ExchangeStrategy::EvenOdd => "evenodd",
+            ExchangeStrategy::AnyPairsHeatBath => "heatbath",
+        };
+        let temperatures: Vec<String> = self
+            .temperatures
+            .iter()
+            .map(|temperature| temperature.to_string())
+            .collect();
+        format!(
+            "model={model}&p={}&size={}&temps={}&exchange={exchange}",
+            self.heads_probability.get(),
+            self.size,
+            temperatures.join(",")
+        )
     }
 
-    pub fn contains_temp(&self, temp: f64) -> bool {
-        for val in self.temperatures.iter() {
-            if val.temperature == temp {
-                return true;
+    /// Parameters that are missing or invalid keep their current value.
+    /// Invalid temperatures are skipped
+    pub fn update_from_query(&mut self, query: &[(&str, &str)]) {
+        for (key, value) in query {
+            match *key {
+                "model" => match *value {
+                    "coins" => self.model = ModelKind::Coins,
+                    "ising" => self.model = ModelKind::IsingChain,
+                    _ => (),
+                },
+                "p" => {
+                    if let Some(p) = value.parse().ok().and_then(HeadsProbability::new) {
+                        self.heads_probability = p;
+                    }
+                }
+                "size" => {
+                    if let Ok(size) = value.parse::<NonZeroU32>() {
+                        self.size = size.min(NonZeroU32::new(MAX_SIZE).unwrap());
+                    }
+                }
+                "temps" => {
+                    let temperatures: Vec<f64> = value
+                        .split(',')
+                        .filter_map(|temperature| temperature.parse::<f64>().ok())
+                        .filter(|temperature| *temperature != 0.0 && temperature.is_finite())
+                        .collect();
+                    // the current ladder is kept if the link has no usable temperature
+                    if !temperatures.is_empty() {
+                        self.temperatures = temperatures;
+                    }
+                }
+                "exchange" => match *value {
+                    "random" => self.exchange_strategy = ExchangeStrategy::RandomPairs,
+                    "evenodd" => self.exchange_strategy = ExchangeStrategy::EvenOdd,
+                    // links created before the strategy was renamed
+                    "heatbath" | "gibbs" => {
+                        self.exchange_strategy = ExchangeStrategy::AnyPairsHeatBath
+                    }
+                    _ => (),
+                },
+                _ => (),
             }
         }
-        false
     }
 }
 
-#[derive(PartialEq, Eq)]
-pub struct SortHelper {
-    temp: NotNan<f64>,
-}
-
-#[allow(clippy::non_canonical_partial_ord_impl)]
-impl PartialOrd for SortHelper {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        let o_positive = other.temp.signum() == 1.0;
-        let s_positive = self.temp.signum() == 1.0;
-
-        match (s_positive, o_positive) {
-            (false, false) => other.temp.partial_cmp(&self.temp),
-            (true, true) => other.temp.partial_cmp(&self.temp),
-            _ => self.temp.partial_cmp(&other.temp),
+impl ParallelTemperingData {
+    pub fn parameters(&self) -> ParallelTemperingParameters {
+        ParallelTemperingParameters {
+            model: self.engine.model().kind,
+            heads_probability: self.engine.model().heads_probability,
+            size: self.engine.size(),
+            temperatures: self
+                .engine
+                .temperatures
+                .iter()
+                .map(|temp| temp.temperature)
+                .collect(),
+            exchange_strategy: self.engine.exchange_strategy,
         }
     }
-}
 
-impl Ord for SortHelper {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
-    }
-}
-
-#[derive(Debug, Default)]
-pub struct AcceptanceCounter {
-    accepted: u64,
-    rejected: u64,
-}
-
-impl AcceptanceCounter {
-    pub fn count_acceptance(&mut self) {
-        self.accepted += 1;
-    }
-
-    pub fn count_rejected(&mut self) {
-        self.rejected += 1;
+    /// Starts a new simulation with the given temperature ladder
+    pub fn apply_parameters(&mut self, parameters: ParallelTemperingParameters) {
+        self.init_cycles();
+        let model = ModelParameters {
+            kind: parameters.model,
+            heads_probability: parameters.heads_probability,
+        };
+        if model != *self.engine.model() {
+            self.new_model(model);
+        }
+        if parameters.size != self.engine.size() {
+            self.new_length(parameters.size);
+        }
+        self.engine.clear();
+        self.independent_runs = None;
+        for temperature in parameters.temperatures {
+            let _ = self.add_temperature(temperature);
+        }
+        self.engine.sort_temps();
+        self.engine.exchange_strategy = parameters.exchange_strategy;
     }
 
-    pub fn acceptance_rate(&self) -> f64 {
-        self.accepted as f64 / (self.accepted + self.rejected) as f64
-    }
+    fn init_cycles(&mut self) {
+        if self.marker_cycle.is_none() {
+            let markers: Vec<_> = MarkerShape::all().collect();
+            let iter = markers.into_iter().cycle();
 
-    pub fn reset(&mut self) {
-        self.accepted = 0;
-        self.rejected = 0;
-    }
-}
-
-#[derive(Debug)]
-pub struct Temperature {
-    temperature: f64,
-    config: Vec<bool>,
-    marker: MarkerShape,
-    color: u8,
-    hist: HistI32Fast,
-    acceptance: AcceptanceCounter,
-    ring_buffer: RingBuffer<(u8, i32)>,
-    // does not change with config changes!
-    temperature_id: u16,
-    num_heads: Option<i32>,
-}
+            self.marker_cycle = Some(Box::new(iter));
+        }
 
-impl Temperature {
-    pub fn adjust_length(&mut self, length: NonZeroI32, rng: &mut Pcg64) {
-        let length_usize = length.get() as usize;
-        if length_usize <= self.config.len() {
-            self.config.truncate(length_usize);
-        } else {
-            let missing = length_usize - self.config.len();
-            let uniform = Uniform::new_inclusive(0.0, 1.0).expect("Will not fail");
-            self.config
-                .extend(uniform.sample_iter(rng).take(missing).map(|v| v <= 0.5));
+        if self.color_cycle.is_none() {
+            let iter = (0..COLORS.len() as u8).cycle();
+            self.color_cycle = Some(Box::new(iter));
         }
-        self.hist = HistI32Fast::new_inclusive(0, length.get()).unwrap();
-        self.acceptance.reset();
-        self.ring_buffer.reset();
-        self.num_heads = None;
     }
 
-    pub fn markov_step(&mut self, rng: &mut Pcg64) {
-        let len = self.config.len();
-        let old_heads = self.number_of_heads();
-        let entry = self.config.choose_mut(rng).unwrap();
-        let old_val = *entry;
-        *entry = rng.random_bool(0.5);
-        let mut new_heads = if old_val == *entry {
-            old_heads
-        } else if old_val {
-            old_heads - 1
-        } else {
-            old_heads + 1
-        };
-
-        let acceptance_prob =
-            ((old_heads - new_heads) as f64 / (self.temperature * len as f64)).exp();
-        if rng.random::<f64>() >= acceptance_prob {
-            // we reject
-            *entry = old_val;
-            new_heads = old_heads;
-            self.acceptance.count_rejected();
-        } else {
-            self.acceptance.count_acceptance();
+    fn add_temperature(&mut self, to_add: f64) -> bool {
+        if self.engine.contains_temp(to_add) {
+            return false;
         }
-        self.num_heads = Some(new_heads);
-        debug_assert_eq!(new_heads, self.number_of_heads());
-        self.ring_buffer.push((self.color, new_heads));
-        self.increment_hist(new_heads);
+        self.engine.add_temperature(
+            to_add,
+            self.marker_cycle.as_mut().unwrap().next().unwrap(),
+            self.color_cycle.as_mut().unwrap().next().unwrap(),
+        )
     }
 
-    pub fn increment_hist(&mut self, val: i32) {
-        self.hist.increment_quiet(val);
+    fn new_length(&mut self, size: NonZeroU32) {
+        self.engine.set_size(size);
+        self.true_density = self.engine.analytic_log10_probability();
     }
 
-    pub fn new(
-        temp: f64,
-        length: NonZeroI32,
-        rng: &mut Pcg64,
-        marker: MarkerShape,
-        color: u8,
-        id: u16,
-    ) -> Self {
-        let config = (0..length.get()).map(|_| rng.random_bool(0.5)).collect();
-        Temperature {
-            temperature: temp,
-            config,
-            marker,
-            hist: HistI32Fast::new_inclusive(0, length.get()).unwrap(),
-            color,
-            acceptance: AcceptanceCounter::default(),
-            ring_buffer: RingBuffer::new(NonZeroUsize::new(2000).unwrap()),
-            temperature_id: id,
-            num_heads: None,
-        }
+    fn new_model(&mut self, model: ModelParameters) {
+        self.engine.set_model(model);
+        self.true_density = self.engine.analytic_log10_probability();
+        // the independent runs would still simulate the old model
+        self.independent_runs = None;
+        self.wham.restart();
     }
 
-    pub fn number_of_heads(&self) -> i32 {
-        match self.num_heads {
-            Some(heads) => heads,
-            None => self.config.iter().filter(|&s| *s).count() as i32,
+    /// Checks whether the file dialogs are finished
+    fn poll_session_files(&mut self, ctx: &egui::Context) {
+        if let Some(pending) = self.pending_load.as_ref() {
+            match pending.poll() {
+                None => ctx.request_repaint(),
+                Some(None) => self.pending_load = None,
+                Some(Some(bytes)) => {
+                    self.pending_load = None;
+                    self.session_message = Some(match self.load_session(&bytes) {
+                        Ok(()) => "Session loaded".to_owned(),
+                        Err(error) => format!("Loading failed: {error}"),
+                    });
+                }
+            }
         }
+        poll_save(&mut self.pending_save, &mut self.session_message, ctx);
+        poll_save(&mut self.pending_export, &mut self.export_message, ctx);
     }
 
-    pub fn heads_rate(&self) -> f64 {
-        self.number_of_heads() as f64 / self.config.len() as f64
+    fn count_shown_plots(&self) -> u8 {
+        self.show_plot.to_num()
+            + self.show_acceptance.to_num()
+            + self.show_autocorrelation.to_num()
+            + self.show_histogram.to_num()
+            + self.show_history.to_num()
+            + self.show_exchange_rate.to_num()
+            + self.show_estimate.to_num()
+            + self.show_replica_flow.to_num()
     }
+}
 
-    pub fn add_rejected_exchange_to_ringbuffer(&mut self) {
-        self.ring_buffer.repeat_last();
-    }
+/// How the estimates of the different temperatures are merged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MergeMethod {
+    /// Average weighted by the hits, the user chooses the z values
+    #[default]
+    Manual,
+    /// Multi histogram reweighting, z values are determined automatically
+    Wham,
+}
+
+#[derive(Debug, Default)]
+pub enum SidePanelView {
+    Shown,
+    Hidden,
+    #[default]
+    Default,
 }
 
 pub fn parallel_tempering_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
     let is_dark_mode = ctx.style().visuals.dark_mode;
     let data: &mut ParallelTemperingData = any.to_something_or_default_mut();
-    if data.marker_cycle.is_none() {
-        let markers: Vec<_> = MarkerShape::all().collect();
-        let iter = markers.into_iter().cycle();
-
-        data.marker_cycle = Some(Box::new(iter));
-    }
-
-    if data.color_cycle.is_none() {
-        let iter = (0..COLORS.len() as u8).cycle();
-        data.color_cycle = Some(Box::new(iter));
-    }
+    data.poll_session_files(ctx);
+    data.init_cycles();
 
     if matches!(data.side_panel, SidePanelView::Default) {
         let screen_width = ctx.content_rect().width();
@@ -417,10 +424,10 @@ pub fn parallel_tempering_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
                                         let to_add = data.temperature_to_add;
                                         let added = data.add_temperature(to_add);
                                         if added{
-                                            data.sort_temps();
+                                            data.engine.sort_temps();
                                             loop{
                                                 data.temperature_to_add /= 2.0;
-                                                if !data.contains_temp(data.temperature_to_add){
+                                                if !data.engine.contains_temp(data.temperature_to_add){
                                                     break;
                                                 }
                                             }
@@ -431,28 +438,88 @@ pub fn parallel_tempering_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
                             }
                         );
 
+                        let old_model = *data.engine.model();
+                        let mut model = old_model;
+                        ui.horizontal(
+                            |ui|
+                            {
+                                ui.label("Model");
+                                ui.radio_value(&mut model.kind, ModelKind::Coins, "Coins")
+                                    .on_hover_text("Sequence of coins, the energy is the number of heads");
+                                ui.radio_value(&mut model.kind, ModelKind::IsingChain, "Ising chain")
+                                    .on_hover_text("Periodic chain of Ising spins, the energy is the number of domain walls");
+                            }
+                        );
+                        if model.kind == ModelKind::Coins {
+                            heads_probability_slider(ui, &mut model.heads_probability);
+                        }
+                        if old_model != model {
+                            data.new_model(model);
+                        }
+
                         ui.horizontal(
                             |ui|
                             {
-                                ui.label("number of Coins");
-                                let old_num = data.num_coins;
+                                ui.label(format!("number of {}", model.kind.element_name()));
+                                let old_num = data.engine.size();
+                                let mut size = old_num;
                                 ui.add(
-                                    egui::DragValue::new(&mut data.num_coins)
-                                ).on_hover_text("Use this to change the size of all configurations, i.e., the number of coins. Will reset histograms etc. since all configurations are changed.");
-                                if old_num != data.num_coins && !data.temperatures.is_empty(){
-                                    data.new_length();
+                                    egui::DragValue::new(&mut size).range(1..=MAX_SIZE)
+                                ).on_hover_text("Use this to change the size of all configurations. Will reset histograms etc. since all configurations are changed.");
+                                if old_num != size {
+                                    data.new_length(size);
+                                }
+                            }
+                        );
+
+                        ui.collapsing(
+                            "Session",
+                            |ui|
+                            {
+                                let busy = data.pending_load.is_some() || data.pending_save.is_some();
+                                ui.horizontal(
+                                    |ui|
+                                    {
+                                        if ui.add_enabled(!busy, Button::new("Save session"))
+                                            .on_hover_text("Saves the temperatures, configurations, statistics and z values as file. In the browser the file is downloaded")
+                                            .clicked()
+                                        {
+                                            data.session_message = None;
+                                            match data.save_session(){
+                                                Ok(text) => {
+                                                    let name = format!("parallel_tempering.{SESSION_EXTENSION}");
+                                                    data.pending_save = Some(save_file(&name, text.into_bytes()));
+                                                },
+                                                Err(error) => {
+                                                    data.session_message = Some(format!("Saving failed: {error}"));
+                                                }
+                                            }
+                                        }
+                                        if ui.add_enabled(!busy, Button::new("Load session"))
+                                            .on_hover_text("Continues a previously saved session. Replaces everything on this page")
+                                            .clicked()
+                                        {
+                                            data.session_message = None;
+                                            data.pending_load = Some(pick_file("Session", &[SESSION_EXTENSION]));
+                                        }
+                                    }
+                                );
+                                if let Some(message) = data.session_message.as_ref(){
+                                    ui.label(message);
                                 }
                             }
                         );
 
-                        if !data.temperatures.is_empty(){
+                        if !data.engine.temperatures.is_empty(){
 
                             ui.label("Which plots to show:");
-                            data.show_plot.radio(ui, "Heads rate");
+                            data.show_plot.radio(ui, model.kind.energy_name());
                             data.show_histogram.radio(ui, "Histogram");
                             data.show_acceptance.radio(ui, "Acceptance Rate");
+                            data.show_autocorrelation.radio(ui, "Autocorrelation");
                             data.show_exchange_rate.radio(ui, "Exchange Rate");
                             data.show_history.radio(ui, "History");
+                            data.show_replica_flow.radio(ui, "Replica flow");
                             data.show_estimate.radio(ui, "Resulting Estimate");
                             if data.show_estimate.is_show(){
                                 let txt = match data.show_z{
@@ -462,6 +529,43 @@ pub fn parallel_tempering_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
                                 if ui.button(txt).highlight().clicked(){
                                     data.show_z.toggle();
                                 }
+                                ui.collapsing(
+                                    "Error bars",
+                                    |ui|
+                                    {
+                                        match data.independent_runs.as_ref() {
+                                            None => {
+                                                ui.horizontal(
+                                                    |ui|
+                                                    {
+                                                        ui.label("Independent runs");
+                                                        ui.add(DragValue::new(&mut data.num_independent_runs).range(2..=32));
+                                                    }
+                                                );
+                                                if ui.button("Start independent runs")
+                                                    .on_hover_text("Simulates copies of the current temperatures with different seeds. The merged estimates of the copies are used for the error bars")
+                                                    .clicked()
+                                                {
+                                                    data.independent_runs = Some(
+                                                        IndependentRuns::new(&mut data.engine, data.num_independent_runs)
+                                                    );
+                                                }
+                                            },
+                                            Some(runs) => {
+                                                ui.label(
+                                                    format!(
+                                                        "{} runs, {} exchange rounds each",
+                                                        runs.len(),
+                                                        runs.exchange_rounds()
+                                                    )
+                                                );
+                                                if ui.button("Stop independent runs").clicked(){
+                                                    data.independent_runs = None;
+                                                }
+                                            }
+                                        }
+                                    }
+                                );
                             }
                         }
 
@@ -472,33 +576,23 @@ pub fn parallel_tempering_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
                                 for tmp in DEFAULT_TEMPERATURES{
                                     let _ = data.add_temperature(tmp);
                                 }
-                                data.sort_temps();
+                                data.engine.sort_temps();
                             }
 
-                        if !data.temperatures.is_empty() && ui.add(
+                        if !data.engine.temperatures.is_empty() && ui.add(
                                 Button::new("Remove all Temperatures")
                             ).clicked()
                         {
                             // cannot be part of he next if statement,
                             // that would result in a bug
-                            data.temperatures.clear();
-                            data.step_counter = 0;
+                            data.engine.clear();
                         }
 
-                        if !data.temperatures.is_empty(){
+                        if !data.engine.temperatures.is_empty(){
 
                             if ui.add(Button::new("Reset Statistics")).clicked()
                             {
-                                data.temperatures.iter_mut()
-                                    .for_each(
-                                        |t|
-                                        {
-                                            t.hist.reset();
-                                            t.acceptance.reset();
-                                            t.ring_buffer.reset();
-                                        }
-                                    );
-                                data.pair_acceptance.reset_counts();
+                                data.engine.reset_statistics();
                             }
 
                             let txt = if data.paused{
@@ -516,11 +610,190 @@ pub fn parallel_tempering_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
                                 data.step_once = true;
                             }
 
+                            ui.add_enabled(
+                                !cfg!(target_arch = "wasm32"),
+                                egui::Checkbox::new(&mut data.engine.parallel, "Parallel replicas")
+                            )
+                                .on_hover_text("Advance the replicas on all cores in between exchanges, every temperature with its own random numbers. Helps for many coins and many temperatures")
+                                .on_disabled_hover_text("Only available in the native version, the browser version runs on a single thread");
+
+                            if !data.paused{
+                                ui.horizontal(
+                                    |ui|
+                                    {
+                                        ui.radio_value(&mut data.speed, Speed::Regular, "Regular Speed");
+                                        ui.radio_value(&mut data.speed, Speed::SlowMotion, "Slow motion");
+                                    }
+                                );
+                                if data.speed.is_slow_motion(){
+                                    ui.horizontal(
+                                        |ui|
+                                        {
+                                            ui.label("Steps per frame:");
+                                            ui.add(
+                                                Slider::new(
+                                                    &mut data.slow_motion_speed,
+                                                    NonZeroUsize::new(1).unwrap()..=NonZeroUsize::new(1024).unwrap()
+                                                )
+                                            );
+                                        }
+                                    );
+                                }
+                            }
+
+                            ui.collapsing(
+                                "Exchanges",
+                                |ui|
+                                {
+                                    let engine = &mut data.engine;
+                                    ui.radio_value(&mut engine.exchange_strategy, ExchangeStrategy::RandomPairs, "Random pairs")
+                                        .on_hover_text("Randomly chosen adjacent pairs, as many attempts as there are pairs");
+                                    ui.radio_value(&mut engine.exchange_strategy, ExchangeStrategy::EvenOdd, "Even/odd pairs")
+                                        .on_hover_text("Alternates between the pairs (0,1), (2,3), ... and (1,2), (3,4), ...");
+                                    ui.radio_value(&mut engine.exchange_strategy, ExchangeStrategy::AnyPairsHeatBath, "Any pairs (heat bath)")
+                                        .on_hover_text("Heat bath swaps of randomly chosen pairs of temperatures, not only adjacent ones. Only adjacent pairs show up in the exchange rates");
+                                    let mut once_per_sweep = engine.exchange_interval.is_none();
+                                    if ui.checkbox(&mut once_per_sweep, "Once per sweep")
+                                        .on_hover_text("Attempt exchanges after every N steps, where N is the size of the configurations")
+                                        .changed()
+                                    {
+                                        engine.exchange_interval = if once_per_sweep{
+                                            None
+                                        } else {
+                                            Some(engine.size())
+                                        };
+                                    }
+                                    if let Some(interval) = engine.exchange_interval.as_mut(){
+                                        ui.horizontal(
+                                            |ui|
+                                            {
+                                                ui.label("Steps between exchanges");
+                                                ui.add(DragValue::new(interval).range(1..=1000000));
+                                            }
+                                        );
+                                    }
+                                }
+                            );
+
+                            ui.collapsing(
+                                "Export",
+                                |ui|
+                                {
+                                    ui.horizontal(
+                                        |ui|
+                                        {
+                                            ui.radio_value(&mut data.export_separator, Separator::Comma, "CSV");
+                                            ui.radio_value(&mut data.export_separator, Separator::Tab, "TSV");
+                                        }
+                                    );
+                                    let separator = data.export_separator;
+                                    let extension = separator.extension();
+                                    let mut table = None;
+                                    ui.horizontal(
+                                        |ui|
+                                        {
+                                            let enabled = data.pending_export.is_none();
+                                            if ui.add_enabled(enabled, Button::new("Export estimates"))
+                                                .on_hover_text("Histograms, estimates of all temperatures, merged estimate and analytic probability, one row per energy level")
+                                                .clicked()
+                                            {
+                                                table = Some((data.estimates_table(separator), "estimates"));
+                                            }
+                                            if ui.add_enabled(enabled, Button::new("Export exchange rates"))
+                                                .on_hover_text("Exchange rates of all pairs of adjacent temperatures")
+                                                .clicked()
+                                            {
+                                                table = Some((data.exchange_rate_table(separator), "exchange_rates"));
+                                            }
+                                        }
+                                    );
+                                    if let Some((table, name)) = table {
+                                        data.export_message = None;
+                                        let file_name = format!("parallel_tempering_{name}.{extension}");
+                                        data.pending_export = Some(save_file(&file_name, table.into_bytes()));
+                                    }
+                                    if let Some(message) = data.export_message.as_ref(){
+                                        ui.label(message);
+                                    }
+                                }
+                            );
+
+                            ui.collapsing(
+                                "Burn-in",
+                                |ui|
+                                {
+                                    let detector = &mut data.engine.equilibration;
+                                    ui.checkbox(&mut detector.enabled, "Automatic reset after burn-in")
+                                        .on_hover_text("Resets the statistics once, as soon as the history of every temperature looks stationary");
+                                    ui.horizontal(
+                                        |ui|
+                                        {
+                                            ui.label("Tolerance");
+                                            ui.add(
+                                                DragValue::new(&mut detector.tolerance)
+                                                    .range(0.0..=10.0)
+                                                    .speed(0.01)
+                                            ).on_hover_text("Largest allowed difference between the averages of the first and second half of the history, in standard errors");
+                                        }
+                                    );
+                                    if detector.phase().is_some() && ui.button("Restart burn-in").clicked(){
+                                        detector.restart();
+                                    }
+                                }
+                            );
+
+                            ui.collapsing(
+                                "Auto-tune temperatures",
+                                |ui|
+                                {
+                                    let tuner = &mut data.engine.ladder_tuner;
+                                    ui.radio_value(&mut tuner.target, TuneTarget::ConstantAcceptance, "Constant exchange rate")
+                                        .on_hover_text("All temperature pairs should have the same exchange rate");
+                                    ui.radio_value(&mut tuner.target, TuneTarget::FeedbackOptimized, "Optimized replica flow")
+                                        .on_hover_text("f(T) should decrease linearly. Needs many round trips, so use more sweeps per iteration");
+                                    ui.horizontal(
+                                        |ui|
+                                        {
+                                            ui.label("Sweeps per iteration");
+                                            ui.add(DragValue::new(&mut tuner.sweeps_per_iteration).range(1..=1000000));
+                                        }
+                                    );
+                                    ui.horizontal(
+                                        |ui|
+                                        {
+                                            ui.label("Iterations");
+                                            ui.add(DragValue::new(&mut tuner.max_iterations).range(1..=1000));
+                                        }
+                                    );
+                                    if tuner.enabled {
+                                        ui.label(
+                                            format!(
+                                                "Iteration {} of {}",
+                                                tuner.iterations_done() + 1,
+                                                tuner.max_iterations
+                                            )
+                                        );
+                                        if ui.button("Stop auto-tune").clicked(){
+                                            tuner.enabled = false;
+                                        }
+                                    } else if data.engine.temperatures.len() < 3 {
+                                        ui.label("At least 3 temperatures are required");
+                                    } else if ui.button("Start auto-tune")
+                                        .on_hover_text("The top and bottom temperatures stay fixed, the others are moved. Statistics are reset after each iteration")
+                                        .clicked()
+                                    {
+                                        tuner.restart();
+                                        tuner.enabled = true;
+                                        data.engine.reset_statistics();
+                                    }
+                                }
+                            );
+
                             ui.label("Adjust/delete temperatures:");
 
 
                             // Adjust top temperature
-                            let mut iter = data.temperatures
+                            let mut iter = data.engine.temperatures
                                 .iter_mut()
                                 .rev();
                             let tmp = iter.next().unwrap();
@@ -565,19 +838,19 @@ pub fn parallel_tempering_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
                                         .range(range);
                                     top(ui, widget)
                                 };
-                                data.remove(to_remove);
+                                data.engine.remove(to_remove);
                             }
 
 
                             // Adjusting Clamped temperatures. Has been debugged already
-                            let current_temperatures: Vec<_> = data.temperatures
+                            let current_temperatures: Vec<_> = data.engine.temperatures
                                 .iter()
                                 .map(|t| t.temperature)
                                 .collect();
 
-                            let mut idx = data.temperatures.len() - 1;
+                            let mut idx = data.engine.temperatures.len() - 1;
                             let windows = current_temperatures.windows(3);
-                            let temperature_iter = data.temperatures
+                            let temperature_iter = data.engine.temperatures
                                 .iter_mut()
                                 .skip(1);
 
@@ -623,10 +896,10 @@ pub fn parallel_tempering_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
                                 }
 
                             }
-                            data.remove(to_remove);
+                            data.engine.remove(to_remove);
 
                             // Adjusting bottom temperature
-                            let mut iter = data.temperatures.iter_mut();
+                            let mut iter = data.engine.temperatures.iter_mut();
                             let tmp = iter.next().unwrap();
                             fn bottom<W>(
                                 ui: &mut egui::Ui,
@@ -678,7 +951,7 @@ pub fn parallel_tempering_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
                                     bottom(ui, widget)
                                 }
                             };
-                            data.remove(to_remove);
+                            data.engine.remove(to_remove);
                         }
 
 
@@ -717,13 +990,51 @@ pub fn parallel_tempering_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
     egui::CentralPanel::default().show(ctx, |ui| {
         // The central panel the region left after adding TopPanel's and SidePanel's
 
-        let mut step_performed = false;
-        if !data.paused || data.step_once {
-            data.temperatures
-                .iter_mut()
-                .for_each(|temp| temp.markov_step(&mut data.rng));
-            // steps were performed if there was at least one config
-            step_performed = !data.temperatures.is_empty();
+        if !data.paused {
+            let max_steps = data
+                .speed
+                .is_slow_motion()
+                .then_some(data.slow_motion_speed);
+            match data.independent_runs.as_mut() {
+                Some(runs) => data
+                    .scheduler
+                    .run(ctx, max_steps, &mut [&mut data.engine, runs]),
+                None => data.scheduler.run(ctx, max_steps, &mut [&mut data.engine]),
+            }
+            ui.label(format!(
+                "{:.3e} steps per second and temperature",
+                data.scheduler.steps_per_second(0)
+            ));
+        } else if data.step_once {
+            data.engine.step();
+        }
+
+        match data.engine.equilibration.phase() {
+            Some(Phase::BurnIn) => {
+                ui.label(colored_text(
+                    "Burn-in: the statistics will be reset once the simulation is equilibrated",
+                    get_color(0, is_dark_mode),
+                ));
+                let stuck = data.engine.equilibration.stuck();
+                if !stuck.is_empty() {
+                    let temps: Vec<String> = stuck.iter().map(|t| format!("{t:.4}")).collect();
+                    ui.label(colored_text(
+                        &format!(
+                            "Stuck at a single energy level, ignored for the burn-in: T = {}",
+                            temps.join(", ")
+                        ),
+                        get_color(3, is_dark_mode),
+                    ))
+                    .on_hover_text("These replicas did not change their energy at all. Their statistics are unreliable, consider changing the temperatures");
+                }
+            }
+            Some(Phase::Production) => {
+                ui.label(colored_text(
+                    "Production: the statistics were measured after the burn-in",
+                    get_color(5, is_dark_mode),
+                ));
+            }
+            None => (),
         }
 
         let mut rect = ui.max_rect();
@@ -739,7 +1050,10 @@ pub fn parallel_tempering_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
         ui.horizontal(|ui| {
             if data.show_plot.is_show() {
                 ui.vertical(|ui| {
-                    ui.label("Current heads rate");
+                    ui.label(format!(
+                        "Current {}",
+                        data.engine.model().kind.energy_name().to_lowercase()
+                    ));
                     show_plot(data, ui, is_dark_mode, smaller_rect);
                 });
             }
@@ -760,11 +1074,17 @@ pub fn parallel_tempering_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
                     show_acceptance_rate(data, ui, is_dark_mode, smaller_rect);
                 });
             }
+            if data.show_autocorrelation.is_show() {
+                ui.vertical(|ui| {
+                    ui.label("Integrated autocorrelation time");
+                    show_autocorrelation_time(data, ui, is_dark_mode, smaller_rect);
+                });
+            }
             if data.show_exchange_rate.is_show() {
                 ui.vertical(|ui| {
                     let exchange_name = format!(
                         "Exchange Rate: (tried exchanges = {})",
-                        data.pair_acceptance.counter
+                        data.engine.pair_acceptance.counter
                     );
                     ui.label(exchange_name);
                     show_exchange_rate(data, ui, smaller_rect, is_dark_mode);
@@ -783,6 +1103,18 @@ pub fn parallel_tempering_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
                     });
             }
 
+            if data.show_replica_flow.is_show() {
+                ui.vertical(|ui| {
+                    let flow_name = format!(
+                        "Replica flow: (round trips = {}, exchange rounds = {})",
+                        data.engine.round_trips(),
+                        data.engine.exchange_rounds()
+                    );
+                    ui.label(flow_name);
+                    show_replica_flow(data, ui, is_dark_mode, smaller_rect);
+                });
+            }
+
             if data.show_estimate.is_show() {
                 ui.vertical(|ui| {
                     ui.label("Resulting Estimate");
@@ -792,19 +1124,6 @@ pub fn parallel_tempering_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
         });
 
         data.step_once = false;
-
-        if step_performed {
-            data.step_counter += 1;
-            if data.step_counter >= data.num_coins.get() {
-                // try exchanges
-                data.step_counter = 0;
-                temp_exchanges(
-                    &mut data.rng,
-                    &mut data.temperatures,
-                    &mut data.pair_acceptance,
-                );
-            }
-        }
     });
 
     ctx.request_repaint();
@@ -816,8 +1135,8 @@ fn show_acceptance_rate(
     is_dark_mode: bool,
     rect: Rect,
 ) {
-    let mut plot_points = Vec::with_capacity(data.temperatures.len());
-    for (id, temp) in data.temperatures.iter().enumerate() {
+    let mut plot_points = Vec::with_capacity(data.engine.temperatures.len());
+    for (id, temp) in data.engine.temperatures.iter().enumerate() {
         let acceptance_rate = temp.acceptance.acceptance_rate();
         plot_points.push(([acceptance_rate, id as f64], (temp.marker, temp.color)));
     }
@@ -834,7 +1153,7 @@ fn show_acceptance_rate(
         [0.0, -0.33],
         [
             1.0 + f64::EPSILON,
-            (data.temperatures.len() - 1).max(1) as f64 + 0.33,
+            (data.engine.temperatures.len() - 1).max(1) as f64 + 0.33,
         ],
     );
 
@@ -844,7 +1163,7 @@ fn show_acceptance_rate(
             if mark.value.fract().abs() < 0.01 {
                 let val = mark.value.round() as isize;
                 if val >= 0 {
-                    match data.temperatures.get(val as usize) {
+                    match data.engine.temperatures.get(val as usize) {
                         Some(tmp) => tmp.temperature.to_string(),
                         None => "".to_owned(),
                     }
@@ -870,15 +1189,159 @@ fn show_acceptance_rate(
         });
 }
 
+fn show_autocorrelation_time(
+    data: &ParallelTemperingData,
+    ui: &mut egui::Ui,
+    is_dark_mode: bool,
+    rect: Rect,
+) {
+    let mut max_tau: f64 = 1.0;
+    let mut plot_points = Vec::with_capacity(data.engine.temperatures.len());
+    for (id, temp) in data.engine.temperatures.iter().enumerate() {
+        if let Some(time) = temp.autocorrelation.integrated_time() {
+            max_tau = max_tau.max(time.tau_int);
+            plot_points.push(([time.tau_int, id as f64], time.reliable, temp));
+        }
+    }
+
+    let all_points = plot_points.into_iter().map(|(plot_data, reliable, temp)| {
+        let plot_points = PlotPoints::new(vec![plot_data]);
+        let name = if reliable {
+            "τ_int"
+        } else {
+            "lower bound of τ_int"
+        };
+        Points::new(name, plot_points)
+            .radius(10.0)
+            .filled(reliable)
+            .shape(temp.marker)
+            .color(get_color(temp.color, is_dark_mode))
+    });
+
+    let plot_bounds = PlotBounds::from_min_max(
+        [0.0, -0.33],
+        [
+            max_tau * 1.1,
+            (data.engine.temperatures.len() - 1).max(1) as f64 + 0.33,
+        ],
+    );
+
+    let y_axis = AxisHints::new_y()
+        .label("Temperature")
+        .formatter(|mark, _| {
+            if mark.value.fract().abs() < 0.01 {
+                let val = mark.value.round() as isize;
+                if val >= 0 {
+                    match data.engine.temperatures.get(val as usize) {
+                        Some(tmp) => tmp.temperature.to_string(),
+                        None => "".to_owned(),
+                    }
+                } else {
+                    "".to_string()
+                }
+            } else {
+                "".to_owned()
+            }
+        });
+
+    Plot::new("autocorrelation_plot")
+        .x_axis_label("τ_int in sweeps")
+        .show_y(false)
+        .custom_y_axes(vec![y_axis])
+        .legend(Legend::default())
+        .width(rect.width())
+        .height(rect.height())
+        .show(ui, |plot_ui| {
+            for points in all_points {
+                plot_ui.points(points);
+            }
+            plot_ui.set_plot_bounds(plot_bounds);
+        });
+}
+
+fn show_replica_flow(
+    data: &ParallelTemperingData,
+    ui: &mut egui::Ui,
+    is_dark_mode: bool,
+    rect: Rect,
+) {
+    let all_points = data
+        .engine
+        .temperatures
+        .iter()
+        .enumerate()
+        .filter_map(|(id, temp)| {
+            let fraction = temp.up_down.up_fraction();
+            (!fraction.is_nan()).then(|| {
+                let plot_points = PlotPoints::new(vec![[fraction, id as f64]]);
+                Points::new("", plot_points)
+                    .radius(10.0)
+                    .shape(temp.marker)
+                    .color(get_color(temp.color, is_dark_mode))
+            })
+        });
+
+    // For an optimal ladder f(T) decreases linearly from the bottom to the top
+    let top = (data.engine.temperatures.len() as f64 - 1.0).max(0.0);
+    let color = match is_dark_mode {
+        true => Color32::WHITE,
+        false => Color32::BLACK,
+    };
+    let ideal = Line::new("", vec![[1.0, 0.0], [0.0, top]])
+        .color(color)
+        .style(egui_plot::LineStyle::dashed_loose());
+
+    let plot_bounds = PlotBounds::from_min_max(
+        [0.0, -0.33],
+        [
+            1.0 + f64::EPSILON,
+            (data.engine.temperatures.len() - 1).max(1) as f64 + 0.33,
+        ],
+    );
+
+    let y_axis = AxisHints::new_y()
+        .label("Temperature")
+        .formatter(|mark, _| {
+            if mark.value.fract().abs() < 0.01 {
+                let val = mark.value.round() as isize;
+                if val >= 0 {
+                    match data.engine.temperatures.get(val as usize) {
+                        Some(tmp) => tmp.temperature.to_string(),
+                        None => "".to_owned(),
+                    }
+                } else {
+                    "".to_string()
+                }
+            } else {
+                "".to_owned()
+            }
+        });
+
+    Plot::new("flow_plot")
+        .x_axis_label("Fraction f(T) of replicas moving up")
+        .show_y(false)
+        .custom_y_axes(vec![y_axis])
+        .width(rect.width())
+        .height(rect.height())
+        .show(ui, |plot_ui| {
+            plot_ui.line(ideal);
+            for points in all_points {
+                plot_ui.points(points);
+            }
+            plot_ui.set_plot_bounds(plot_bounds);
+        });
+}
+
 fn show_exchange_rate(
     data: &ParallelTemperingData,
     ui: &mut egui::Ui,
     rect: Rect,
     is_dark_mode: bool,
 ) {
-    let mut plot_points = Vec::with_capacity(data.temperatures.len());
-    for (id, temp_slice) in data.temperatures.windows(2).enumerate() {
+    let mut plot_points = Vec::with_capacity(data.engine.temperatures.len());
+    for (id, temp_slice) in data.engine.temperatures.windows(2).enumerate() {
         let acceptance_rate = data
+            .engine
             .pair_acceptance
             .get_pair_acceptance(temp_slice[0].temperature_id, temp_slice[1].temperature_id);
         match acceptance_rate {
@@ -903,11 +1366,12 @@ fn show_exchange_rate(
         [0.0, -0.33],
         [
             1.0 + f64::EPSILON,
-            (data.temperatures.len() as isize - 2).max(1) as f64 + 0.33,
+            (data.engine.temperatures.len() as isize - 2).max(1) as f64 + 0.33,
         ],
     );
 
     let y_labels: Vec<_> = data
+        .engine
         .temperatures
         .windows(2)
         .map(|slice| format!("{} vs {}", slice[1].temperature, slice[0].temperature))
@@ -948,10 +1412,10 @@ fn show_exchange_rate(
 
 fn show_plot(data: &ParallelTemperingData, ui: &mut egui::Ui, is_dark_mode: bool, rect: Rect) {
     let mut plot_points: Vec<([f64; 2], (MarkerShape, u8))> =
-        Vec::with_capacity(data.temperatures.len());
-    for (id, temp) in data.temperatures.iter().enumerate() {
-        let heads_rate = temp.heads_rate();
-        plot_points.push(([heads_rate, id as f64], (temp.marker, temp.color)));
+        Vec::with_capacity(data.engine.temperatures.len());
+    for (id, temp) in data.engine.temperatures.iter().enumerate() {
+        let energy = temp.energy();
+        plot_points.push(([energy, id as f64], (temp.marker, temp.color)));
     }
 
     let all_points = plot_points.into_iter().map(|(plot_data, plot_config)| {
@@ -966,7 +1430,7 @@ fn show_plot(data: &ParallelTemperingData, ui: &mut egui::Ui, is_dark_mode: bool
         [0.0, -0.33],
         [
             1.0 + f64::EPSILON,
-            (data.temperatures.len() - 1).max(1) as f64 + 0.33,
+            (data.engine.temperatures.len() - 1).max(1) as f64 + 0.33,
         ],
     );
 
@@ -976,7 +1440,7 @@ fn show_plot(data: &ParallelTemperingData, ui: &mut egui::Ui, is_dark_mode: bool
             if mark.value.fract().abs() < 0.01 {
                 let val = mark.value.round() as isize;
                 if val >= 0 {
-                    match data.temperatures.get(val as usize) {
+                    match data.engine.temperatures.get(val as usize) {
                         Some(tmp) => tmp.temperature.to_string(),
                         None => "".to_owned(),
                     }
@@ -989,7 +1453,7 @@ fn show_plot(data: &ParallelTemperingData, ui: &mut egui::Ui, is_dark_mode: bool
         });
 
     Plot::new("my_plot")
-        .x_axis_label("Heads rate")
+        .x_axis_label(data.engine.model().kind.energy_name())
         .show_y(false)
         .custom_y_axes(vec![y_axis])
         .width(rect.width())
@@ -1008,12 +1472,12 @@ fn show_history_plot(
     is_dark_mode: bool,
     rect: Rect,
 ) {
-    let min_height = 0.99 * rect.height() / (data.temperatures.len() as f32);
+    let min_height = 0.99 * rect.height() / (data.engine.temperatures.len() as f32);
     Grid::new("HistoryGrid")
         .min_row_height(min_height)
         .min_col_width(rect.width())
         .show(ui, |ui| {
-            for (id, temp) in data.temperatures.iter().enumerate().rev() {
+            for (id, temp) in data.engine.temperatures.iter().enumerate().rev() {
                 let mut lines: Vec<Line> = Vec::new();
                 let start = -(temp.ring_buffer.len() as i16);
                 let mut iter = temp.ring_buffer.iter().zip(start..).peekable();
@@ -1045,7 +1509,7 @@ fn show_history_plot(
                 let mut plot = Plot::new(format!("{id}PastPLOT"))
                     .legend(Legend::default())
                     .allow_scroll(false)
-                    .y_axis_label("#Heads");
+                    .y_axis_label(data.engine.model().kind.level_name());
 
                 if id == 0 {
                     plot = plot.x_axis_label("time");
@@ -1067,12 +1531,12 @@ fn show_history_plot(
 }
 
 fn show_hist(data: &ParallelTemperingData, ui: &mut egui::Ui, is_dark_mode: bool, rect: Rect) {
-    let min_height = 0.99 * rect.height() / (data.temperatures.len() as f32);
+    let min_height = 0.99 * rect.height() / (data.engine.temperatures.len() as f32);
     Grid::new("HistGrid")
         .min_row_height(min_height)
         .min_col_width(rect.width())
         .show(ui, |ui| {
-            for (id, temp) in data.temperatures.iter().enumerate().rev() {
+            for (id, temp) in data.engine.temperatures.iter().enumerate().rev() {
                 let chart = BarChart::new(
                     format!("T={}", temp.temperature),
                     temp.hist
@@ -1091,7 +1555,7 @@ fn show_hist(data: &ParallelTemperingData, ui: &mut egui::Ui, is_dark_mode: bool
                     .y_axis_label("Hits");
 
                 if id == 0 {
-                    plot = plot.x_axis_label("Number of Heads");
+                    plot = plot.x_axis_label(data.engine.model().kind.level_name());
                 }
 
                 plot.show(ui, |plot_ui| {
@@ -1102,74 +1566,28 @@ fn show_hist(data: &ParallelTemperingData, ui: &mut egui::Ui, is_dark_mode: bool
         });
 }
 
-fn temp_exchanges(
-    rng: &mut Pcg64,
-    temperatures: &mut [Temperature],
-    pair_acceptance: &mut PairAcceptance,
+/// Checks whether the file is written and stores the result in `message`
+fn poll_save(
+    pending: &mut Option<PendingFile<Option<std::io::Result<()>>>>,
+    message: &mut Option<String>,
+    ctx: &egui::Context,
 ) {
-    if temperatures.len() < 2 {
-        return;
-    }
-    let num_pairs = temperatures.len() - 1;
-
-    for _ in 0..num_pairs {
-        let lower = rng.random_range(0..num_pairs);
-        let mut iter = temperatures.iter_mut().skip(lower);
-        let a = iter.next().unwrap();
-        let b = iter.next().unwrap();
-        let exchange_prob = exchange_acceptance_probability(a, b);
-        if exchange_prob >= rng.random() {
-            exchange_temperatures(a, b);
-            pair_acceptance.count_acceptance(a.temperature_id, b.temperature_id);
-        } else {
-            a.add_rejected_exchange_to_ringbuffer();
-            b.add_rejected_exchange_to_ringbuffer();
-            pair_acceptance.count_rejected(a.temperature_id, b.temperature_id);
+    if let Some(file) = pending.as_ref() {
+        match file.poll() {
+            None => ctx.request_repaint(),
+            Some(result) => {
+                *pending = None;
+                *message = match result {
+                    None => None,
+                    Some(Ok(())) => Some("Saved".to_owned()),
+                    Some(Err(error)) => Some(format!("Saving failed: {error}")),
+                };
+            }
         }
     }
-    pair_acceptance.count_exchange_try();
-}
-
-fn exchange_temperatures(a: &mut Temperature, b: &mut Temperature) {
-    swap(&mut a.marker, &mut b.marker);
-    swap(&mut a.config, &mut b.config);
-    swap(&mut a.color, &mut b.color);
-    swap(&mut a.num_heads, &mut b.num_heads);
-
-    let ea = a.number_of_heads();
-    let eb = b.number_of_heads();
-
-    a.hist.increment_quiet(ea);
-    a.ring_buffer.push((a.color, ea));
-    b.hist.increment_quiet(eb);
-    b.ring_buffer.push((b.color, eb));
-}
-
-fn exchange_acceptance_probability(a: &Temperature, b: &Temperature) -> f64 {
-    assert!(
-        SortHelper {
-            temp: NotNan::new(a.temperature).unwrap()
-        } <= SortHelper {
-            temp: NotNan::new(b.temperature).unwrap()
-        },
-        "{a:?} {b:?}"
-    );
-    //assert!(
-    //    a.temperature <= b.temperature
-    //);
-    let ea = a.heads_rate();
-    let eb = b.heads_rate();
-    1.0_f64.min(((1.0 / a.temperature - 1.0 / b.temperature) * (ea - eb)).exp())
-}
-
-pub enum ToRemove {
-    Nothing,
-    Top,
-    Bottom,
-    Idx(usize),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
 pub enum Show {
     Yes,
     #[default]
@@ -1229,75 +1647,25 @@ pub const fn get_color(idx: u8, is_dark_mode: bool) -> Color32 {
     COLORS[idx as usize].get_color(is_dark_mode)
 }
 
-#[derive(Default)]
-pub struct PairAcceptance {
-    map: BTreeMap<(u16, u16), AcceptanceCounter>,
-    counter: usize,
-}
-
-impl PairAcceptance {
-    pub fn update_pairs(&mut self, temps: &[Temperature]) {
-        let retain_set: BTreeSet<_> = temps
-            .windows(2)
-            .map(|slice| {
-                let a = slice[0].temperature_id;
-                let b = slice[1].temperature_id;
-                if a < b {
-                    (a, b)
-                } else {
-                    (b, a)
-                }
-            })
-            .collect();
-        let other_set: BTreeSet<_> = self.map.keys().copied().collect();
-        for to_remove in other_set.difference(&retain_set) {
-            self.map.remove(to_remove);
-        }
-        for to_add in retain_set.difference(&other_set) {
-            self.map.insert(*to_add, AcceptanceCounter::default());
-        }
-    }
-
-    pub fn count_rejected(&mut self, id1: u16, id2: u16) {
-        self.get_mut(id1, id2).count_rejected();
-    }
-
-    pub fn count_acceptance(&mut self, id1: u16, id2: u16) {
-        self.get_mut(id1, id2).count_acceptance();
-    }
-
-    fn get_mut(&mut self, id1: u16, id2: u16) -> &mut AcceptanceCounter {
-        let (a, b) = if id1 < id2 { (id1, id2) } else { (id2, id1) };
-        match self.map.get_mut(&(a, b)) {
-            None => unreachable!(),
-            Some(counter) => counter,
-        }
-    }
-
-    pub fn get_pair_acceptance(&self, id1: u16, id2: u16) -> Option<&AcceptanceCounter> {
-        let (a, b) = if id1 < id2 { (id1, id2) } else { (id2, id1) };
-        self.map.get(&(a, b))
-    }
-
-    pub fn reset_counts(&mut self) {
-        for v in self.map.values_mut() {
-            v.reset();
-        }
-        self.counter = 0;
-    }
-
-    pub fn count_exchange_try(&mut self) {
-        self.counter += 1;
-    }
-}
-
 pub struct ResultingEstimate {
     pdfs: Vec<Vec<f64>>,
 }
 
+/// How well the estimates of two adjacent temperatures agree
+pub struct PairOverlap {
+    /// Mean of lower minus upper estimate on the overlapping bins
+    mean_difference: f64,
+    /// Root mean square of the difference on the overlapping bins,
+    /// after the mean difference is removed, i.e., what is left after [ResultingEstimate::align_z]
+    mismatch: f64,
+    /// Number of overlapping bins
+    bins: usize,
+}
+
 impl ResultingEstimate {
     pub fn calc(data: &ParallelTemperingData) -> Self {
         let mut pdfs: Vec<Vec<_>> = data
+            .engine
             .temperatures
             .iter()
             .map(|temp| {
@@ -1305,17 +1673,17 @@ impl ResultingEstimate {
                 let hits = hist.hist().as_slice();
                 let count: usize = hits.iter().sum();
                 let factor = (count as f64).recip();
-                let len_factor = (hits.len() as f64).recip();
+                let len_factor = ((hits.len() - 1) as f64).recip();
                 let temperature_recip = temp.temperature.recip();
                 hits.iter()
                     .enumerate()
                     .map(|(idx, hits)| {
                         let prob = *hits as f64 * factor;
-                        let heads_rate = idx as f64 * len_factor;
+                        let energy = idx as f64 * len_factor;
                         if prob == 0.0 {
                             f64::NAN
                         } else {
-                            (prob).ln() + heads_rate * temperature_recip
+                            (prob).ln() + energy * temperature_recip
                         }
                     })
                     .collect()
@@ -1330,7 +1698,7 @@ impl ResultingEstimate {
     }
 
     pub fn merged(&self, data: &ParallelTemperingData) -> Vec<f64> {
-        let mut iter = data.temperatures.iter().map(|t| t.hist.hist());
+        let mut iter = data.engine.temperatures.iter().map(|t| t.hist.hist());
         let cloned_iter = iter.clone();
 
         let mut total_hits_hist = iter.next().unwrap().clone();
@@ -1366,6 +1734,60 @@ impl ResultingEstimate {
         prob
     }
 
+    /// Compares the estimates of adjacent temperatures on all bins where both were hit.
+    /// None if there are no such bins
+    pub fn pair_overlaps(&self) -> Vec<Option<PairOverlap>> {
+        self.pdfs
+            .windows(2)
+            .map(|pair| {
+                let differences: Vec<f64> = pair[0]
+                    .iter()
+                    .zip(pair[1].iter())
+                    .map(|(lower, upper)| lower - upper)
+                    .filter(|diff| !diff.is_nan())
+                    .collect();
+                if differences.is_empty() {
+                    return None;
+                }
+                let factor = (differences.len() as f64).recip();
+                let mean_difference = differences.iter().sum::<f64>() * factor;
+                let variance = differences
+                    .iter()
+                    .map(|d| (d - mean_difference).powi(2))
+                    .sum::<f64>()
+                    * factor;
+                Some(PairOverlap {
+                    mean_difference,
+                    mismatch: variance.sqrt(),
+                    bins: differences.len(),
+                })
+            })
+            .collect()
+    }
+
+    /// Changes the z values such that the squared difference of adjacent estimates
+    /// on their overlapping bins is minimized.
+    /// Starting from the temperature with index `reference`, whose z value is not changed,
+    /// the shifts are chained to both ends.
+    /// Pairs without overlap keep their relative shift
+    pub fn align_z(&self, z: &mut [f64], reference: usize) {
+        let overlaps = self.pair_overlaps();
+        let mut shifts = vec![0.0; z.len()];
+        for idx in reference..overlaps.len() {
+            let offset = overlaps[idx]
+                .as_ref()
+                .map_or(0.0, |overlap| overlap.mean_difference);
+            shifts[idx + 1] = shifts[idx] + offset;
+        }
+        for idx in (0..reference.min(overlaps.len())).rev() {
+            let offset = overlaps[idx]
+                .as_ref()
+                .map_or(0.0, |overlap| overlap.mean_difference);
+            shifts[idx] = shifts[idx + 1] - offset;
+        }
+        z.iter_mut().zip(shifts).for_each(|(z, shift)| *z += shift);
+    }
+
     fn show(
         data: &mut ParallelTemperingData,
         ui: &mut egui::Ui,
@@ -1373,17 +1795,26 @@ impl ResultingEstimate {
         rect: Rect,
         ctx: &egui::Context,
     ) {
-        if data.temperatures.is_empty() {
+        if data.engine.temperatures.is_empty() {
             return;
         }
 
         // Make sure I have exactly enough z values
-        if data.z.len() != data.temperatures.len() {
-            data.z.truncate(data.temperatures.len());
-            let missing = data.temperatures.len() - data.z.len();
+        if data.z.len() != data.engine.temperatures.len() {
+            data.z.truncate(data.engine.temperatures.len());
+            let missing = data.engine.temperatures.len() - data.z.len();
             data.z.extend(std::iter::repeat_n(0.0, missing));
         }
 
+        let wham_estimate = match data.merge_method {
+            MergeMethod::Manual => None,
+            MergeMethod::Wham => {
+                let estimate = data.wham.solve(&data.engine.temperatures);
+                data.z = data.wham.z();
+                Some(estimate)
+            }
+        };
+
         let this = Self::calc(data);
 
         if data.show_z.is_show() {
@@ -1396,20 +1827,62 @@ impl ResultingEstimate {
                         data.show_z.toggle();
                     }
                     data.z_legend.radio(ui, "z legend");
+                    data.rate_function.radio(ui, "rate function");
 
-                    let hint = colored_text(
-                        "Adjust the z values to make the curves overlap!",
-                        get_color(1, is_dark_mode),
-                    );
-                    ui.label(hint);
-                    let hint = colored_text(
-                        "Drag the values to quickly adjust them",
-                        get_color(5, is_dark_mode),
-                    );
-                    ui.label(hint);
+                    ui.horizontal(|ui| {
+                        ui.label("Merge method:");
+                        ui.radio_value(&mut data.merge_method, MergeMethod::Manual, "Manual z");
+                        ui.radio_value(&mut data.merge_method, MergeMethod::Wham, "WHAM")
+                            .on_hover_text("Multi histogram reweighting: The z values are determined self-consistently from all histograms");
+                    });
+
+                    match data.merge_method {
+                        MergeMethod::Manual => {
+                            let hint = colored_text(
+                                "Adjust the z values to make the curves overlap!",
+                                get_color(1, is_dark_mode),
+                            );
+                            ui.label(hint);
+                            let hint = colored_text(
+                                "Drag the values to quickly adjust them",
+                                get_color(5, is_dark_mode),
+                            );
+                            ui.label(hint);
+
+                            data.z_reference = data.z_reference.min(data.z.len() - 1);
+                            ui.horizontal(|ui| {
+                                let temperatures = &data.engine.temperatures;
+                                egui::ComboBox::from_label("stays fixed")
+                                    .selected_text(format!(
+                                        "T={}",
+                                        temperatures[data.z_reference].temperature
+                                    ))
+                                    .show_ui(ui, |ui| {
+                                        for (idx, temp) in temperatures.iter().enumerate() {
+                                            ui.selectable_value(
+                                                &mut data.z_reference,
+                                                idx,
+                                                format!("T={}", temp.temperature),
+                                            );
+                                        }
+                                    });
+                                if ui
+                                    .button("Align automatically")
+                                    .on_hover_text("Minimizes the squared difference of adjacent curves where they overlap")
+                                    .clicked()
+                                {
+                                    this.align_z(&mut data.z, data.z_reference);
+                                }
+                            });
+                        }
+                        MergeMethod::Wham => {
+                            show_wham_convergence(&mut data.wham, ui, is_dark_mode);
+                        }
+                    }
 
                     let mut max_len = 0;
                     let labels: Vec<_> = data
+                        .engine
                         .temperatures
                         .iter()
                         .map(|temp| {
@@ -1418,12 +1891,43 @@ impl ResultingEstimate {
                             label
                         })
                         .collect();
+                    let manual = data.merge_method == MergeMethod::Manual;
                     for (z, this_label) in data.z.iter_mut().zip(labels) {
                         ui.horizontal(|ui| {
                             label(ui, this_label, max_len, Some("z="));
-                            ui.add(DragValue::new(z).speed(0.1));
+                            ui.add_enabled(manual, DragValue::new(z).speed(0.1));
                         });
                     }
+
+                    ui.separator();
+                    ui.label("Mismatch of adjacent curves where they overlap:");
+                    Grid::new("z_mismatch_grid").striped(true).show(ui, |ui| {
+                        for (pair, overlap) in data
+                            .engine
+                            .temperatures
+                            .windows(2)
+                            .zip(this.pair_overlaps())
+                        {
+                            ui.label(format!(
+                                "T={} vs T={}",
+                                pair[1].temperature, pair[0].temperature
+                            ));
+                            match overlap {
+                                Some(overlap) => {
+                                    ui.label(format!("{} bins", overlap.bins));
+                                    ui.label(format!("rms = {:.4}", overlap.mismatch));
+                                }
+                                None => {
+                                    ui.label(colored_text(
+                                        "no overlap",
+                                        get_color(0, is_dark_mode),
+                                    ));
+                                    ui.label("");
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
                 });
         }
         ui.vertical(|ui| {
@@ -1432,7 +1936,7 @@ impl ResultingEstimate {
             halfed_rect.set_height(height * 0.5);
 
             let mut plot = Plot::new("my_est_plot")
-                .x_axis_label("Heads rate")
+                .x_axis_label(data.engine.model().kind.energy_name())
                 .y_axis_label("Log10 of Probability")
                 .show_y(false)
                 .width(halfed_rect.width())
@@ -1442,9 +1946,9 @@ impl ResultingEstimate {
             }
 
             plot.show(ui, |plot_ui| {
-                for (temp, pdf) in data.temperatures.iter().zip(this.pdfs.iter()) {
+                for (temp, pdf) in data.engine.temperatures.iter().zip(this.pdfs.iter()) {
                     let len = pdf.len();
-                    let factor = (len as f64).recip();
+                    let factor = ((len - 1) as f64).recip();
                     let line = Line::new(
                         format!("T={}", temp.temperature),
                         pdf.iter()
@@ -1457,41 +1961,176 @@ impl ResultingEstimate {
                 }
             });
 
-            let merged = this.merged(data);
+            let bins = this.pdfs.first().map_or(0, |pdf| pdf.len());
+            let mut error_estimate = data
+                .independent_runs
+                .as_mut()
+                .map(|runs| runs.estimate())
+                .filter(|estimate| estimate.mean.len() == bins);
+
+            let (mut merged, merged_name) = match wham_estimate {
+                Some(estimate) => (estimate, "Merged (WHAM)"),
+                None => (this.merged(data), "Merged"),
+            };
+
+            let mut true_density = data.true_density.clone();
+            let model = *data.engine.model();
+            let (x_scale, x_label, y_label, rate_function_limit) = if data.rate_function.is_show() {
+                // Φ = -(1/N) ln P over level / max_level
+                let size = data.engine.size().get();
+                log10_slice_to_rate(&mut merged, size);
+                if let Some(density) = true_density.as_mut() {
+                    log10_slice_to_rate(density, size);
+                }
+                if let Some(estimate) = error_estimate.as_mut() {
+                    log10_slice_to_rate(&mut estimate.mean, size);
+                    estimate
+                        .std_error
+                        .iter_mut()
+                        .for_each(|error| *error *= std::f64::consts::LN_10 / size as f64);
+                }
+                // the limit N → ∞ is only drawn for the coins
+                let limit = (model.kind == ModelKind::Coins).then(|| {
+                    Line::new(
+                        "Rate function for N → ∞",
+                        binomial_rate_points(model.heads_probability.get()),
+                    )
+                    .style(egui_plot::LineStyle::dashed_loose())
+                });
+                (
+                    ((bins.max(2) - 1) as f64).recip(),
+                    model.kind.energy_name(),
+                    "Rate function Φ",
+                    limit,
+                )
+            } else {
+                (1.0, model.kind.level_name(), "Log10 of Probability", None)
+            };
 
             Plot::new("my_est_res_plot")
-                .x_axis_label("Number of Heads")
-                .y_axis_label("Log10 of Probability")
+                .x_axis_label(x_label)
+                .y_axis_label(y_label)
                 .show_y(false)
                 .width(halfed_rect.width())
                 .height(halfed_rect.height())
                 .legend(Legend::default())
                 .show(ui, |plot_ui| {
                     let line: Line = Line::new(
-                        "Merged",
+                        merged_name,
                         merged
                             .iter()
                             .enumerate()
-                            .map(|(idx, val)| [idx as f64, *val])
-                            .collect::<Vec<_>>(),
-                    );
-
-                    let line2 = Line::new(
-                        "Analytic Probability",
-                        data.true_density
-                            .iter()
-                            .enumerate()
-                            .map(|(idx, val)| [idx as f64, *val])
+                            .map(|(idx, val)| [idx as f64 * x_scale, *val])
                             .collect::<Vec<_>>(),
                     );
 
-                    plot_ui.line(line2);
+                    if let Some(true_density) = true_density.as_ref() {
+                        let line2 = Line::new(
+                            "Analytic Probability",
+                            true_density
+                                .iter()
+                                .enumerate()
+                                .map(|(idx, val)| [idx as f64 * x_scale, *val])
+                                .collect::<Vec<_>>(),
+                        );
+                        plot_ui.line(line2);
+                    }
                     plot_ui.line(line);
+                    if let Some(limit) = rate_function_limit {
+                        plot_ui.line(limit);
+                    }
+
+                    if let Some(estimate) = error_estimate {
+                        show_error_band(plot_ui, &estimate, x_scale, is_dark_mode);
+                    }
                 });
         });
     }
 }
 
+/// `x_scale` is the factor between the index of a bin and the x axis
+fn show_error_band(
+    plot_ui: &mut egui_plot::PlotUi,
+    estimate: &ErrorEstimate,
+    x_scale: f64,
+    is_dark_mode: bool,
+) {
+    let color = get_color(5, is_dark_mode);
+    let name = "Mean of independent runs ± standard error";
+    let bounds: Vec<_> = estimate
+        .mean
+        .iter()
+        .zip(estimate.std_error.iter())
+        .map(|(mean, error)| (mean - error, mean + error))
+        .collect();
+    // one trapezoid per pair of adjacent bins, as egui only fills convex polygons
+    for (idx, pair) in bounds.windows(2).enumerate() {
+        let (low_a, high_a) = pair[0];
+        let (low_b, high_b) = pair[1];
+        if [low_a, high_a, low_b, high_b].iter().any(|v| v.is_nan()) {
+            continue;
+        }
+        let x = idx as f64 * x_scale;
+        let next_x = (idx + 1) as f64 * x_scale;
+        let polygon = egui_plot::Polygon::new(
+            name,
+            PlotPoints::new(vec![
+                [x, low_a],
+                [next_x, low_b],
+                [next_x, high_b],
+                [x, high_a],
+            ]),
+        )
+        .fill_color(color.gamma_multiply(0.4))
+        .stroke(egui::Stroke::NONE);
+        plot_ui.polygon(polygon);
+    }
+    let mean: Vec<_> = estimate
+        .mean
+        .iter()
+        .enumerate()
+        .map(|(idx, val)| [idx as f64 * x_scale, *val])
+        .collect();
+    plot_ui.points(Points::new(name, mean).radius(2.0).color(color));
+}
+
+fn show_wham_convergence(wham: &mut Wham, ui: &mut egui::Ui, is_dark_mode: bool) {
+    let status = if wham.is_converged() {
+        format!("WHAM converged after {} iterations", wham.history.len())
+    } else {
+        format!(
+            "WHAM did not converge yet, {} iterations this frame",
+            wham.history.len()
+        )
+    };
+    ui.horizontal(|ui| {
+        ui.label(status);
+        if ui
+            .button("Restart")
+            .on_hover_text("Start the iteration from scratch to see the full convergence")
+            .clicked()
+        {
+            wham.restart();
+        }
+    });
+
+    let points: Vec<_> = wham
+        .history
+        .iter()
+        .enumerate()
+        .filter(|(_, change)| **change > 0.0)
+        .map(|(idx, change)| [idx as f64 + 1.0, change.log10()])
+        .collect();
+    Plot::new("wham_convergence")
+        .x_axis_label("Iteration")
+        .y_axis_label("Log10 of max change")
+        .width(300.0)
+        .height(150.0)
+        .show(ui, |plot_ui| {
+            plot_ui.line(Line::new("", points).color(get_color(0, is_dark_mode)));
+        });
+}
+
 const PAR_TEMP_HELP_MSG: &str =
 "This program is intended to visualize parallel tempering.
 
@@ -1502,25 +2141,68 @@ You can also click on the 'Add Example Temperatures' button, which will add some
 You can adjust the number of coins in the coin flip sequence by dragging the corresponding number. Note: This will reset the statistics, as all configurations are\
 changed fundamentally.
 
+Besides sequences of coins, where the energy is the number of heads, you can also choose a periodic Ising chain as model. \
+There the energy is the number of domain walls, i.e., of neighboring spins that point in different directions. \
+As the number of domain walls on a ring is always even, the plots show the number of pairs of domain walls. \
+Positive temperatures favor ferromagnetic configurations, negative temperatures antiferromagnetic configurations. \
+Changing the model replaces all configurations and resets the statistics.
+
 Once at least one temperature is added you can display the plots. Use the radio buttons to choose which plots to show.
 
-Heads rate: Displays current heads rate of the configurations
+Heads rate: Displays current heads rate of the configurations, or the density of domain walls for the Ising chain
 Histogram: Displays the histograms of all temperatures
 Acceptance rate: Displays the measured acceptance rate of the markov steps
+Autocorrelation: Displays the integrated autocorrelation time of the energy level, e.g., the number of heads, for each temperature, measured in sweeps. \
+A sweep consists of as many markov steps as there are coins. The window of the estimate is chosen automatically. \
+Hollow markers are only lower bounds, i.e., the simulation did not run long enough compared to the autocorrelation time. \
+Once the simulation ran for many autocorrelation times, the initial configuration is forgotten and you can reset the statistics.
 Exchange rate: Displays the measured acceptance rate of configuration swaps between temperature pairs
 History: Displays the heads rate of the last 2000 steps if available
+Resulting Estimate: Displays the reweighted estimates of all temperatures and the merged estimate. \
+Open the z selection to either shift the curves by hand, align them automatically by least squares or let the multi histogram reweighting (WHAM) \
+determine the shifts. The table at the bottom shows how well adjacent curves agree and which of them do not overlap at all.
+Use 'Error bars' to simulate independent copies of the current temperatures. The merged estimates of the copies \
+are used to show the statistical error of the estimate, which grows in the tails of the distribution.
+Replica flow: Every configuration is a replica, which keeps its identity while traveling through the temperatures. \
+A replica is moving up, if it visited the bottom temperature more recently than the top temperature, and moving down otherwise. \
+The plot displays the fraction f(T) of replicas moving up for each temperature. \
+For a well chosen set of temperatures f(T) decreases linearly from the bottom to the top, as indicated by the dashed line. \
+A round trip is counted each time a replica travels from the bottom to the top temperature and back.
 
 The colors in the plots are representing the different configurations, i.e., if a proposed configuration change is accepted, the colors also change.
 
 Use the 'Reset statistics' button if you want to reset the histograms etc., for example because you want to restart the statistics measurement 
 after the equilibration time has passed.
+Alternatively enable 'Automatic reset after burn-in' in the 'Burn-in' section. Then the history of every temperature is divided into ten blocks. \
+Once the average of the first five blocks agrees with the average of the last five blocks within the tolerance, measured in standard errors, \
+for all temperatures, the statistics are reset once and the plots are marked as production. \
+The burn-in starts again whenever the temperatures or the configurations change.
 
 You can use the 'pause' button to enter single step mode, where you are able to perform markov steps manually via clicking a button.
+In the desktop version 'Parallel replicas' lets the replicas advance on all cores in between exchanges, \
+which keeps the program responsive for many coins and many temperatures.
+Otherwise you can choose between regular speed, where the simulation runs as fast as possible, and slow motion, \
+where you choose how many markov steps are performed per frame.
 
 You can also adjust the temperatures in the plot to get a feeling for the effect. The temperatures are always clamped between the temperatures around it.
 This action does not reset the statistics, such that you can get a feel for what the changes in temperature do. Feel free to reset the statistics with the corresponding button.
 
 If you just want to delete specific temperatures: Click on the trash icon next to the temperature.
+
+Use 'Save session' to store everything on this page, i.e., the temperatures, the configurations, the statistics, the z values \
+and which plots are shown, as file. In the browser the file is downloaded. \
+'Load session' continues from such a file, for example to prepare interesting states in advance.
+Under 'Export' you can save the numbers behind the plots as CSV or TSV tables to analyze them with your own tools: \
+The histograms together with the estimates of all temperatures, the merged estimate and the analytic probability, \
+or the exchange rates of all pairs of adjacent temperatures.
+
+Instead of adjusting the temperatures by hand you can also let the program do it via 'Auto-tune temperatures'. \
+The top and bottom temperatures stay fixed, the temperatures in between are moved according to the measured statistics. \
+Either such that all pairs have the same exchange rate or such that the flow of replicas is optimized. \
+After each iteration the statistics are reset.
+Under 'Exchanges' you can choose which exchanges of configurations are attempted: Randomly chosen adjacent pairs, \
+alternating even and odd pairs, or heat bath exchanges between all pairs of temperatures. \
+By default exchanges are attempted once per sweep, uncheck 'Once per sweep' to choose the number of markov steps between exchanges.
 You can also click on the 'Remove all Temperatures' button if you wish to try something completely different.
 ";
 
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Largest lag, in sweeps, for which the autocorrelation function is measured
+pub const MAX_LAG: usize = 500;
+/// Window parameter of the automatic windowing, see Sokal,
+/// "Monte Carlo Methods in Statistical Mechanics: Foundations and New Algorithms" (1996)
+const WINDOW_FACTOR: f64 = 5.0;
+/// The estimate is only trusted if the time series is this many windows long
+const MIN_WINDOWS: u64 = 20;
+
+/// # Online estimator of the autocorrelation function
+///
+/// Every new value is correlated with the last [MAX_LAG] values,
+/// so nothing but the sums has to be stored
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AutocorrelationEstimator {
+    /// Last values, newest at the back
+    history: VecDeque<f64>,
+    /// Sum of x(t) * x(t - lag) for every lag
+    lag_sums: Vec<f64>,
+    /// How often every lag was measured
+    lag_counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+    /// Values are shifted by the first value to reduce rounding errors
+    offset: Option<f64>,
+}
+
+/// Integrated autocorrelation time in sweeps
+#[derive(Debug, Clone, Copy)]
+pub struct AutocorrelationTime {
+    pub tau_int: f64,
+    /// Window chosen by the automatic windowing
+    pub window: usize,
+    /// False if no window fulfilling the criterion was found.
+    /// Then tau_int is only a lower bound
+    pub reliable: bool,
+}
+
+impl AutocorrelationEstimator {
+    pub fn push(&mut self, value: f64) {
+        let offset = *self.offset.get_or_insert(value);
+        let value = value - offset;
+        if self.history.len() == MAX_LAG {
+            self.history.pop_front();
+        }
+        self.history.push_back(value);
+        let lags = self.history.len();
+        if self.lag_sums.len() < lags {
+            self.lag_sums.resize(lags, 0.0);
+            self.lag_counts.resize(lags, 0);
+        }
+        for (lag, past) in self.history.iter().rev().enumerate() {
+            self.lag_sums[lag] += value * past;
+            self.lag_counts[lag] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Normalized autocorrelation function for all measured lags.
+    /// Empty if the variance is not known yet
+    pub fn autocorrelation_function(&self) -> Vec<f64> {
+        if self.count < 2 {
+            return Vec::new();
+        }
+        let mean = self.sum / self.count as f64;
+        let mean_squared = mean * mean;
+        let covariances: Vec<f64> = self
+            .lag_sums
+            .iter()
+            .zip(self.lag_counts.iter())
+            .map(|(sum, count)| sum / *count as f64 - mean_squared)
+            .collect();
+        let variance = covariances[0];
+        if variance <= 0.0 {
+            return Vec::new();
+        }
+        covariances.iter().map(|c| c / variance).collect()
+    }
+
+    /// Integrated autocorrelation time via automatic windowing:
+    /// The window M is the smallest M with M >= c * tau_int(M)
+    pub fn integrated_time(&self) -> Option<AutocorrelationTime> {
+        let rho = self.autocorrelation_function();
+        if rho.is_empty() {
+            return None;
+        }
+        let mut tau_int = 0.5;
+        for (window, value) in rho.iter().enumerate().skip(1) {
+            tau_int += value;
+            if window as f64 >= WINDOW_FACTOR * tau_int {
+                return Some(AutocorrelationTime {
+                    tau_int,
+                    window,
+                    reliable: self.count >= MIN_WINDOWS * window as u64,
+                });
+            }
+        }
+        Some(AutocorrelationTime {
+            tau_int,
+            window: rho.len() - 1,
+            reliable: false,
+        })
+    }
+}
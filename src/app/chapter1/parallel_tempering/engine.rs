@@ -0,0 +1,747 @@
+use super::{
+    temp_exchanges, update_replica_flow, AutocorrelationEstimator, EnergyModel,
+    EquilibrationDetector, ExchangeStrategy, LadderTuner, Replica, UpDownCounter,
+};
+use crate::dark_magic::{Backend, BackgroundComputation, TimeSliced, Work};
+use crate::misc::*;
+use derivative::Derivative;
+use egui_plot::MarkerShape;
+use ordered_float::NotNan;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+use sampling::{HistI32Fast, Histogram};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    mem::swap,
+    num::{NonZeroU32, NonZeroUsize},
+    sync::OnceLock,
+    time::Duration,
+};
+use web_time::Instant;
+
+/// Steps of every replica per [Work::work_chunk] of a [ReplicaBatch]
+const BATCH_CHUNK_STEPS: u32 = 1000;
+/// Handing the replicas to the workers and waiting for them takes some time,
+/// which only pays off if all replicas together perform at least this many steps
+const MIN_PARALLEL_STEPS: usize = 10000;
+
+/// # Parallel tempering of any [EnergyModel]
+///
+/// Contains the whole state of the simulation and does not know anything about egui,
+/// the GUI only renders the state.
+///
+/// Each step performs one markov step for every temperature.
+/// `size` steps are a sweep.
+/// By default the exchange of configurations between
+/// temperatures is attempted once per sweep.
+#[derive(Derivative, Serialize, Deserialize)]
+#[derivative(Default(bound = ""))]
+pub struct ParallelTempering<M: EnergyModel> {
+    /// Sorted via [SortHelper], see [ParallelTempering::sort_temps]
+    pub temperatures: Vec<Temperature<M>>,
+    pub pair_acceptance: PairAcceptance,
+    #[derivative(Default(value = "Pcg64::seed_from_u64(832147)"))]
+    pub rng: Pcg64,
+    /// Number of elements, e.g. coins, of every configuration
+    #[derivative(Default(value = "NonZeroU32::new(100).unwrap()"))]
+    size: NonZeroU32,
+    model: M::Parameters,
+    /// Steps performed since the last exchange attempt
+    step_counter: u32,
+    /// Steps performed since the last complete sweep
+    sweep_step_counter: u32,
+    /// Sweeps since the last reset of the statistics
+    sweeps: u64,
+    pub exchange_strategy: ExchangeStrategy,
+    /// Steps between rounds of exchange attempts. None: once per sweep
+    pub exchange_interval: Option<NonZeroU32>,
+    /// Which pairs are used by [ExchangeStrategy::EvenOdd] next
+    even_round: bool,
+    /// Advance the replicas on all cores in between exchanges and sweeps.
+    /// Every temperature then uses its own random number generator, seeded once from `rng`
+    #[serde(default)]
+    pub parallel: bool,
+    /// One per core, reused for every exchange interval while `parallel` is set
+    #[serde(skip, default = "Vec::new")]
+    workers: Vec<BackgroundComputation<ReplicaBatch<M>>>,
+    /// id for the next temperature that is added
+    next_temperature_id: u16,
+    /// Completed round trips of all replicas, see [update_replica_flow]
+    round_trips: u64,
+    /// Number of rounds of exchange attempts since the last reset of the statistics
+    exchange_rounds: u64,
+    /// Optimizes the temperatures, if enabled
+    pub ladder_tuner: LadderTuner,
+    /// Resets the statistics after the burn-in, if enabled
+    pub equilibration: EquilibrationDetector,
+}
+
+impl<M: EnergyModel> ParallelTempering<M> {
+    pub fn new(model: M::Parameters, size: NonZeroU32, rng: Pcg64) -> Self {
+        Self {
+            model,
+            size,
+            rng,
+            ..Default::default()
+        }
+    }
+
+    /// Same temperatures, model and size, but new random configurations
+    /// and without any statistics
+    pub fn independent_copy(&self, rng: Pcg64) -> Self {
+        let mut copy = Self::new(self.model.clone(), self.size, rng);
+        for temp in self.temperatures.iter() {
+            copy.add_temperature(temp.temperature, temp.marker, temp.color);
+        }
+        copy.sort_temps();
+        copy.exchange_strategy = self.exchange_strategy;
+        copy.exchange_interval = self.exchange_interval;
+        copy
+    }
+
+    pub fn size(&self) -> NonZeroU32 {
+        self.size
+    }
+
+    /// Changes the size of all configurations.
+    /// Resets histograms etc. since all configurations are changed.
+    pub fn set_size(&mut self, size: NonZeroU32) {
+        self.size = size;
+        self.temperatures.iter_mut().for_each(|temp| {
+            temp.resize(size, &mut self.rng);
+        });
+        self.pair_acceptance.reset_counts();
+        self.reset_replica_flow();
+        self.equilibration.restart();
+    }
+
+    pub fn model(&self) -> &M::Parameters {
+        &self.model
+    }
+
+    /// Replaces all configurations by random configurations of the new model.
+    /// Resets histograms etc.
+    pub fn set_model(&mut self, model: M::Parameters) {
+        self.model = model;
+        self.temperatures.iter_mut().for_each(|temp| {
+            temp.replace_config(M::random(&self.model, self.size, &mut self.rng));
+        });
+        self.pair_acceptance.reset_counts();
+        self.reset_replica_flow();
+        self.equilibration.restart();
+    }
+
+    /// Log10 of the probability of every energy level at infinite temperature, if known
+    pub fn analytic_log10_probability(&self) -> Option<Vec<f64>> {
+        M::analytic_log10_probability(&self.model, self.size)
+    }
+
+    /// Adds temperature, if it does not exist yet. Does not sort the temperatures!
+    ///
+    /// Returns true if the temperature was added
+    pub fn add_temperature(&mut self, to_add: f64, marker: MarkerShape, color: u8) -> bool {
+        if !self.contains_temp(to_add) {
+            let config = M::random(&self.model, self.size, &mut self.rng);
+            self.temperatures.push(Temperature::new(
+                to_add,
+                config,
+                marker,
+                color,
+                self.next_temperature_id,
+            ));
+            self.next_temperature_id += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn remove(&mut self, to_remove: ToRemove) {
+        match to_remove {
+            ToRemove::Nothing => return,
+            ToRemove::Top => {
+                self.temperatures.pop();
+            }
+            ToRemove::Bottom => {
+                self.temperatures.remove(0);
+            }
+            ToRemove::Idx(idx) => {
+                self.temperatures.remove(idx);
+            }
+        }
+        self.pair_acceptance.update_pairs(&self.temperatures);
+        self.reset_replica_flow();
+        self.equilibration.restart();
+    }
+
+    /// Makes a simulation that was loaded from a file consistent:
+    /// Recomputes the energy levels, rebuilds the pair statistics
+    /// and makes sure that the ids of new temperatures are unused.
+    ///
+    /// Returns an error if the simulation cannot be used
+    pub fn repair_loaded(&mut self) -> Result<(), String> {
+        let expected_max_level =
+            M::random(&self.model, self.size, &mut Pcg64::seed_from_u64(0)).max_level();
+        let mut ids = BTreeSet::new();
+        for temp in self.temperatures.iter_mut() {
+            if !temp.temperature.is_finite() || temp.temperature == 0.0 {
+                return Err(format!("Invalid temperature {}", temp.temperature));
+            }
+            if !ids.insert(temp.temperature_id) {
+                return Err(format!(
+                    "Temperature id {} is used twice",
+                    temp.temperature_id
+                ));
+            }
+            if !temp.config.refresh(&self.model) || temp.config.max_level() != expected_max_level {
+                return Err("Configurations do not match the model".to_owned());
+            }
+            if temp.hist.hist().len() != expected_max_level as usize + 1 {
+                return Err("Histograms do not match the configurations".to_owned());
+            }
+        }
+        if let Some(max_id) = ids.last() {
+            self.next_temperature_id = self.next_temperature_id.max(max_id.saturating_add(1));
+        }
+        let sorted = self.temperatures.is_sorted_by_key(|temp| SortHelper {
+            temp: NotNan::new(temp.temperature).unwrap(),
+        });
+        if sorted {
+            self.pair_acceptance.update_pairs(&self.temperatures);
+        } else {
+            self.sort_temps();
+        }
+        Ok(())
+    }
+
+    /// Removes all temperatures
+    pub fn clear(&mut self) {
+        self.temperatures.clear();
+        self.pair_acceptance.update_pairs(&self.temperatures);
+        self.step_counter = 0;
+        self.sweep_step_counter = 0;
+        self.round_trips = 0;
+        self.exchange_rounds = 0;
+        self.sweeps = 0;
+        self.equilibration.restart();
+    }
+
+    pub fn sort_temps(&mut self) {
+        self.temperatures.sort_by_cached_key(|a| SortHelper {
+            temp: NotNan::new(a.temperature).unwrap(),
+        });
+        self.pair_acceptance.update_pairs(&self.temperatures);
+        self.reset_replica_flow();
+        self.equilibration.restart();
+    }
+
+    pub fn contains_temp(&self, temp: f64) -> bool {
+        for val in self.temperatures.iter() {
+            if val.temperature == temp {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Resets histograms, acceptance rates, history and exchange rates
+    pub fn reset_statistics(&mut self) {
+        self.temperatures.iter_mut().for_each(|t| {
+            t.hist.reset();
+            t.acceptance.reset();
+            t.ring_buffer.reset();
+            t.up_down.reset();
+            t.replica.round_trips = 0;
+            t.autocorrelation.reset();
+        });
+        self.pair_acceptance.reset_counts();
+        self.round_trips = 0;
+        self.exchange_rounds = 0;
+        self.sweeps = 0;
+    }
+
+    /// The ends of the ladder changed, so the directions of the replicas are meaningless
+    fn reset_replica_flow(&mut self) {
+        self.temperatures.iter_mut().for_each(|t| {
+            t.up_down.reset();
+            t.replica.reset();
+        });
+        self.round_trips = 0;
+        self.exchange_rounds = 0;
+        self.sweeps = 0;
+    }
+
+    /// Completed round trips bottom -> top -> bottom of all replicas
+    pub fn round_trips(&self) -> u64 {
+        self.round_trips
+    }
+
+    /// Rounds of exchange attempts since the statistics were reset
+    pub fn exchange_rounds(&self) -> u64 {
+        self.exchange_rounds
+    }
+
+    /// Sweeps since the statistics were reset
+    pub fn sweeps(&self) -> u64 {
+        self.sweeps
+    }
+
+    /// Performs one markov step for every temperature.
+    /// Exchanges are attempted every `exchange_interval` steps.
+    pub fn step(&mut self) {
+        self.advance(1);
+    }
+
+    /// Performs up to `max_steps` markov steps for every temperature,
+    /// but stops at the next exchange or the end of the sweep,
+    /// as the replicas are only independent in between.
+    ///
+    /// Returns the number of performed steps
+    fn advance(&mut self, max_steps: u32) -> u32 {
+        let interval = self.exchange_interval.unwrap_or(self.size).get();
+        let steps = max_steps
+            .min(interval.saturating_sub(self.step_counter))
+            .min(self.size.get().saturating_sub(self.sweep_step_counter))
+            .max(1);
+        if self.temperatures.is_empty() {
+            return steps;
+        }
+        if self.parallel {
+            self.advance_in_parallel(steps);
+        } else {
+            for _ in 0..steps {
+                self.temperatures
+                    .iter_mut()
+                    .for_each(|temp| temp.markov_step(&mut self.rng));
+            }
+        }
+        self.step_counter += steps;
+        if self.step_counter >= interval {
+            self.step_counter = 0;
+            temp_exchanges(
+                self.exchange_strategy,
+                self.even_round,
+                &mut self.rng,
+                &mut self.temperatures,
+                &mut self.pair_acceptance,
+            );
+            self.even_round = !self.even_round;
+            self.round_trips += update_replica_flow(&mut self.temperatures);
+            self.exchange_rounds += 1;
+        }
+        self.sweep_step_counter += steps;
+        if self.sweep_step_counter >= self.size.get() {
+            self.sweep_step_counter = 0;
+            self.sweeps += 1;
+            self.temperatures.iter_mut().for_each(|temp| {
+                let level = temp.energy_level() as f64;
+                temp.autocorrelation.push(level);
+            });
+            if self.ladder_tuner.is_due(self.sweeps)
+                && self
+                    .ladder_tuner
+                    .iterate(&mut self.temperatures, &self.pair_acceptance)
+            {
+                self.reset_statistics();
+                // the temperatures moved, so the replicas have to equilibrate again
+                self.equilibration.restart();
+            } else if !self.ladder_tuner.enabled && self.equilibration.check(&self.temperatures) {
+                self.reset_statistics();
+            }
+        }
+        steps
+    }
+
+    /// Splits the replicas into one batch per core and lets the long-lived `workers`
+    /// advance every batch by `steps`. Waits until all of them are done,
+    /// as the next exchange needs every replica.
+    ///
+    /// On a single core, in the browser or for only a few steps,
+    /// the replicas are advanced right here instead
+    fn advance_in_parallel(&mut self, steps: u32) {
+        for temp in self.temperatures.iter_mut() {
+            if temp.rng.is_none() {
+                temp.rng = Some(Pcg64::from_rng(&mut self.rng));
+            }
+        }
+        let total_steps = steps as usize * self.temperatures.len();
+        let batches = cores().min(self.temperatures.len());
+        if batches < 2 || total_steps < MIN_PARALLEL_STEPS {
+            self.temperatures
+                .iter_mut()
+                .for_each(|temp| temp.markov_steps_own_rng(steps));
+            return;
+        }
+        while self.workers.len() < batches {
+            let batch = ReplicaBatch {
+                replicas: Vec::new(),
+                steps_left: 0,
+            };
+            self.workers
+                .push(BackgroundComputation::new(batch, Backend::Thread));
+        }
+        let batch_len = self.temperatures.len().div_ceil(batches);
+        let used = self.temperatures.len().div_ceil(batch_len);
+        let mut replicas = std::mem::take(&mut self.temperatures).into_iter();
+        for worker in self.workers[..used].iter() {
+            let batch: Vec<_> = replicas.by_ref().take(batch_len).collect();
+            worker.feed(move |work| {
+                work.replicas = batch;
+                work.steps_left = steps;
+            });
+        }
+        // the replicas are put back in any case, so the ladder survives a panic
+        let mut panic = None;
+        for worker in self.workers[..used].iter() {
+            if let Err(payload) = worker.wait() {
+                panic.get_or_insert(payload);
+            }
+            let batch = worker.access(|work| std::mem::take(&mut work.replicas));
+            self.temperatures.extend(batch);
+        }
+        if let Some(payload) = panic {
+            self.workers.clear();
+            std::panic::resume_unwind(payload);
+        }
+    }
+
+    /// Performs steps until `duration` has passed.
+    ///
+    /// Returns the number of performed steps
+    pub fn run_for(&mut self, duration: Duration) -> usize {
+        self.step_budget(Instant::now() + duration) as usize
+    }
+
+    /// Performs `n` sweeps, i.e., `n * size` steps
+    pub fn run_sweeps(&mut self, n: usize) {
+        let mut remaining = n * self.size.get() as usize;
+        while remaining > 0 {
+            let max_steps = remaining.min(u32::MAX as usize) as u32;
+            remaining -= self.advance(max_steps) as usize;
+        }
+    }
+}
+
+impl<M: EnergyModel> TimeSliced for ParallelTempering<M> {
+    fn step_budget(&mut self, deadline: Instant) -> u64 {
+        let mut steps = 0;
+        while Instant::now() < deadline {
+            steps += self.advance(u32::MAX) as u64;
+        }
+        steps
+    }
+
+    /// Single steps, such that the history can be followed
+    fn step_budget_limited(&mut self, deadline: Instant, max_steps: NonZeroUsize) -> u64 {
+        let mut steps = 0;
+        while steps < max_steps.get() as u64 {
+            self.step();
+            steps += 1;
+            if Instant::now() >= deadline {
+                break;
+            }
+        }
+        steps
+    }
+}
+
+/// Replicas that are independent until the next exchange
+struct ReplicaBatch<M> {
+    replicas: Vec<Temperature<M>>,
+    steps_left: u32,
+}
+
+impl<M: EnergyModel> Work for ReplicaBatch<M> {
+    /// Steps every replica still has to perform
+    type Snapshot = u32;
+
+    fn work_chunk(&mut self) -> bool {
+        let steps = self.steps_left.min(BATCH_CHUNK_STEPS);
+        self.replicas
+            .iter_mut()
+            .for_each(|temp| temp.markov_steps_own_rng(steps));
+        self.steps_left -= steps;
+        self.steps_left > 0
+    }
+
+    fn snapshot(&self) -> Self::Snapshot {
+        self.steps_left
+    }
+}
+
+/// Number of cores, 1 in the browser
+fn cores() -> usize {
+    // looking this up involves reading files, which is too slow to be done every time
+    static CORES: OnceLock<usize> = OnceLock::new();
+    *CORES.get_or_init(|| std::thread::available_parallelism().map_or(1, NonZeroUsize::get))
+}
+
+pub enum ToRemove {
+    Nothing,
+    Top,
+    Bottom,
+    Idx(usize),
+}
+
+#[derive(PartialEq, Eq)]
+pub struct SortHelper {
+    pub temp: NotNan<f64>,
+}
+
+#[allow(clippy::non_canonical_partial_ord_impl)]
+impl PartialOrd for SortHelper {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        let o_positive = other.temp.signum() == 1.0;
+        let s_positive = self.temp.signum() == 1.0;
+
+        match (s_positive, o_positive) {
+            (false, false) => other.temp.partial_cmp(&self.temp),
+            (true, true) => other.temp.partial_cmp(&self.temp),
+            _ => self.temp.partial_cmp(&other.temp),
+        }
+    }
+}
+
+impl Ord for SortHelper {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.partial_cmp(other).unwrap()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AcceptanceCounter {
+    accepted: u64,
+    rejected: u64,
+}
+
+impl AcceptanceCounter {
+    pub fn count_acceptance(&mut self) {
+        self.accepted += 1;
+    }
+
+    pub fn count_rejected(&mut self) {
+        self.rejected += 1;
+    }
+
+    pub fn acceptance_rate(&self) -> f64 {
+        self.accepted as f64 / self.attempts() as f64
+    }
+
+    pub fn attempts(&self) -> u64 {
+        self.accepted + self.rejected
+    }
+
+    pub fn reset(&mut self) {
+        self.accepted = 0;
+        self.rejected = 0;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Temperature<M> {
+    pub temperature: f64,
+    pub config: M,
+    #[serde(with = "marker_shape_index")]
+    pub marker: MarkerShape,
+    pub color: u8,
+    pub hist: HistI32Fast,
+    pub acceptance: AcceptanceCounter,
+    pub ring_buffer: RingBuffer<(u8, i32)>,
+    // does not change with config changes!
+    pub temperature_id: u16,
+    /// Travels with the config
+    pub replica: Replica,
+    /// Visits of replicas moving up or down
+    pub up_down: UpDownCounter,
+    /// Of the energy level, measured once per sweep
+    pub autocorrelation: AutocorrelationEstimator,
+    /// Own random number generator, only used if the replicas advance in parallel.
+    /// Belongs to the temperature, not to the configuration
+    #[serde(skip)]
+    rng: Option<Pcg64>,
+}
+
+impl<M: EnergyModel> Temperature<M> {
+    pub fn resize(&mut self, size: NonZeroU32, rng: &mut Pcg64) {
+        self.config.resize(size, rng);
+        self.reset_for_new_config();
+    }
+
+    /// Replaces the configuration, e.g., by one of another model
+    pub fn replace_config(&mut self, config: M) {
+        self.config = config;
+        self.reset_for_new_config();
+    }
+
+    fn reset_for_new_config(&mut self) {
+        self.hist = new_hist(&self.config);
+        self.acceptance.reset();
+        self.ring_buffer.reset();
+        self.autocorrelation.reset();
+    }
+
+    /// Performs `steps` markov steps with the own random number generator,
+    /// which has to be seeded beforehand
+    fn markov_steps_own_rng(&mut self, steps: u32) {
+        let mut rng = self.rng.take().expect("the engine seeds the generator");
+        for _ in 0..steps {
+            self.markov_step(&mut rng);
+        }
+        self.rng = Some(rng);
+    }
+
+    pub fn markov_step(&mut self, rng: &mut Pcg64) {
+        let old_level = self.config.level();
+        let mut new_level = self.config.propose(rng);
+
+        let acceptance_prob = ((old_level - new_level) as f64
+            / (self.temperature * self.config.max_level() as f64))
+            .exp();
+        if rng.random::<f64>() >= acceptance_prob {
+            // we reject
+            self.config.undo();
+            new_level = old_level;
+            self.acceptance.count_rejected();
+        } else {
+            self.acceptance.count_acceptance();
+        }
+        debug_assert_eq!(new_level, self.energy_level());
+        self.ring_buffer.push((self.color, new_level));
+        self.increment_hist(new_level);
+    }
+
+    pub fn increment_hist(&mut self, val: i32) {
+        self.hist.increment_quiet(val);
+    }
+
+    pub fn new(temp: f64, config: M, marker: MarkerShape, color: u8, id: u16) -> Self {
+        Temperature {
+            temperature: temp,
+            hist: new_hist(&config),
+            config,
+            marker,
+            color,
+            acceptance: AcceptanceCounter::default(),
+            ring_buffer: RingBuffer::new(NonZeroUsize::new(2000).unwrap()),
+            temperature_id: id,
+            replica: Replica::new(id),
+            up_down: UpDownCounter::default(),
+            autocorrelation: AutocorrelationEstimator::default(),
+            rng: None,
+        }
+    }
+
+    pub fn energy_level(&self) -> i32 {
+        self.config.level()
+    }
+
+    /// The energy that enters the Boltzmann weight, between 0 and 1
+    pub fn energy(&self) -> f64 {
+        self.energy_level() as f64 / self.config.max_level() as f64
+    }
+
+    pub fn add_rejected_exchange_to_ringbuffer(&mut self) {
+        self.ring_buffer.repeat_last();
+    }
+
+    /// Exchanges the configurations, i.e., everything that belongs to the replica
+    pub fn swap_configuration(&mut self, other: &mut Self) {
+        swap(&mut self.marker, &mut other.marker);
+        swap(&mut self.config, &mut other.config);
+        swap(&mut self.color, &mut other.color);
+        swap(&mut self.replica, &mut other.replica);
+    }
+}
+
+/// [MarkerShape] does not implement serde, so it is stored as its index in [MarkerShape::all]
+mod marker_shape_index {
+    use egui_plot::MarkerShape;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        marker: &MarkerShape,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let idx = MarkerShape::all()
+            .position(|shape| shape == *marker)
+            .expect("every marker shape is contained");
+        serializer.serialize_u8(idx as u8)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<MarkerShape, D::Error> {
+        let idx = u8::deserialize(deserializer)?;
+        MarkerShape::all()
+            .nth(idx as usize)
+            .ok_or_else(|| D::Error::custom("invalid marker shape"))
+    }
+}
+
+/// Histogram containing every energy level of `config`
+fn new_hist<M: EnergyModel>(config: &M) -> HistI32Fast {
+    HistI32Fast::new_inclusive(0, config.max_level()).unwrap()
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct PairAcceptance {
+    map: BTreeMap<(u16, u16), AcceptanceCounter>,
+    pub counter: usize,
+}
+
+impl PairAcceptance {
+    pub fn update_pairs<M>(&mut self, temps: &[Temperature<M>]) {
+        let retain_set: BTreeSet<_> = temps
+            .windows(2)
+            .map(|slice| {
+                let a = slice[0].temperature_id;
+                let b = slice[1].temperature_id;
+                if a < b {
+                    (a, b)
+                } else {
+                    (b, a)
+                }
+            })
+            .collect();
+        let other_set: BTreeSet<_> = self.map.keys().copied().collect();
+        for to_remove in other_set.difference(&retain_set) {
+            self.map.remove(to_remove);
+        }
+        for to_add in retain_set.difference(&other_set) {
+            self.map.insert(*to_add, AcceptanceCounter::default());
+        }
+    }
+
+    pub fn count_rejected(&mut self, id1: u16, id2: u16) {
+        self.get_mut(id1, id2).count_rejected();
+    }
+
+    pub fn count_acceptance(&mut self, id1: u16, id2: u16) {
+        self.get_mut(id1, id2).count_acceptance();
+    }
+
+    fn get_mut(&mut self, id1: u16, id2: u16) -> &mut AcceptanceCounter {
+        let (a, b) = if id1 < id2 { (id1, id2) } else { (id2, id1) };
+        match self.map.get_mut(&(a, b)) {
+            None => unreachable!(),
+            Some(counter) => counter,
+        }
+    }
+
+    pub fn get_pair_acceptance(&self, id1: u16, id2: u16) -> Option<&AcceptanceCounter> {
+        let (a, b) = if id1 < id2 { (id1, id2) } else { (id2, id1) };
+        self.map.get(&(a, b))
+    }
+
+    pub fn reset_counts(&mut self) {
+        for v in self.map.values_mut() {
+            v.reset();
+        }
+        self.counter = 0;
+    }
+
+    pub fn count_exchange_try(&mut self) {
+        self.counter += 1;
+    }
+}
@@ -0,0 +1,124 @@
+use super::{EnergyModel, Temperature};
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+
+/// Number of blocks the history of every temperature is divided into
+const BLOCKS: usize = 10;
+
+/// Whether the statistics still contain the initial configurations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Phase {
+    #[default]
+    BurnIn,
+    /// The statistics were reset after the burn-in
+    Production,
+}
+
+/// # Automatic detection of the end of the burn-in
+///
+/// The history of every temperature, i.e., its ring buffer, is divided into blocks.
+/// Once the averages of the energy levels of the first and second half of the blocks agree
+/// for every temperature, the simulation is considered to be equilibrated
+/// and the statistics are reset once.
+#[derive(Debug, Derivative, Serialize, Deserialize)]
+#[derivative(Default)]
+pub struct EquilibrationDetector {
+    pub enabled: bool,
+    /// Largest allowed difference between the averages of the first and second half
+    /// of the history, in units of its standard error
+    #[derivative(Default(value = "2.0"))]
+    pub tolerance: f64,
+    phase: Phase,
+    /// See [EquilibrationDetector::stuck]
+    #[serde(skip)]
+    stuck: Vec<f64>,
+}
+
+impl EquilibrationDetector {
+    /// None if the detection is disabled
+    pub fn phase(&self) -> Option<Phase> {
+        self.enabled.then_some(self.phase)
+    }
+
+    /// The configurations changed, e.g., because temperatures were added, so the burn-in starts again
+    pub fn restart(&mut self) {
+        self.phase = Phase::BurnIn;
+        self.stuck.clear();
+    }
+
+    /// Temperatures whose replica did not change its energy level within its whole history,
+    /// as of the last check. They are ignored when deciding whether the simulation is equilibrated
+    pub fn stuck(&self) -> &[f64] {
+        &self.stuck
+    }
+
+    /// Returns true exactly once, when the end of the burn-in is detected.
+    /// The statistics should be reset then
+    pub fn check<M: EnergyModel>(&mut self, temperatures: &[Temperature<M>]) -> bool {
+        if !self.enabled || self.phase == Phase::Production || temperatures.is_empty() {
+            return false;
+        }
+        self.stuck.clear();
+        let mut equilibrated = true;
+        for temp in temperatures {
+            match block_test(temp, self.tolerance) {
+                BlockTest::Stationary => (),
+                BlockTest::Stuck => self.stuck.push(temp.temperature),
+                BlockTest::TooShort | BlockTest::Drifting => equilibrated = false,
+            }
+        }
+        if equilibrated {
+            self.phase = Phase::Production;
+        }
+        equilibrated
+    }
+}
+
+enum BlockTest {
+    /// The ring buffer is not full yet
+    TooShort,
+    /// All block averages are identical, so the replica never left its energy level
+    Stuck,
+    Stationary,
+    Drifting,
+}
+
+/// Compares the average of the first half of the blocks with the average of the second half.
+/// The standard error of that difference is estimated from the scatter of the block averages.
+/// Only the most recent history that fills all blocks completely is used
+fn block_test<M: EnergyModel>(temp: &Temperature<M>, tolerance: f64) -> BlockTest {
+    let block_len = temp.ring_buffer.len() / BLOCKS;
+    // the ring buffer of a loaded session can be shorter than the number of blocks
+    if !temp.ring_buffer.is_full() || block_len == 0 {
+        return BlockTest::TooShort;
+    }
+    let skip = temp.ring_buffer.len() - BLOCKS * block_len;
+    let levels: Vec<i32> = temp
+        .ring_buffer
+        .iter()
+        .skip(skip)
+        .map(|(_, level)| *level)
+        .collect();
+    let averages: Vec<f64> = levels
+        .chunks_exact(block_len)
+        .map(|block| block.iter().sum::<i32>() as f64 / block_len as f64)
+        .collect();
+    let mean = averages.iter().sum::<f64>() / BLOCKS as f64;
+    let variance = averages
+        .iter()
+        .map(|average| (average - mean).powi(2))
+        .sum::<f64>()
+        / (BLOCKS - 1) as f64;
+    if variance == 0.0 {
+        return BlockTest::Stuck;
+    }
+    let half = BLOCKS / 2;
+    let first = averages[..half].iter().sum::<f64>() / half as f64;
+    let second = averages[half..].iter().sum::<f64>() / half as f64;
+    let std_error = (2.0 * variance / half as f64).sqrt();
+    if (first - second).abs() <= tolerance * std_error {
+        BlockTest::Stationary
+    } else {
+        BlockTest::Drifting
+    }
+}
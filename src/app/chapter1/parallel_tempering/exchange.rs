@@ -0,0 +1,148 @@
+use super::{EnergyModel, PairAcceptance, SortHelper, Temperature};
+use ordered_float::NotNan;
+use rand::Rng;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+
+/// # Which exchanges of configurations are attempted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ExchangeStrategy {
+    /// As many randomly chosen adjacent pairs as there are pairs
+    #[default]
+    RandomPairs,
+    /// Alternating between the pairs (0,1), (2,3), ... and the pairs (1,2), (3,4), ...
+    EvenOdd,
+    /// Heat bath swaps of randomly chosen pairs, which do not have to be adjacent.
+    /// A pair is swapped with probability `w_swapped / (w_swapped + w_current)`.
+    /// Only the adjacent pairs are counted in the [PairAcceptance]
+    #[serde(alias = "AllPairsGibbs")]
+    AnyPairsHeatBath,
+}
+
+/// One round of exchange attempts
+///
+/// * `even_round` is only used for [ExchangeStrategy::EvenOdd]
+pub fn temp_exchanges<M: EnergyModel>(
+    strategy: ExchangeStrategy,
+    even_round: bool,
+    rng: &mut Pcg64,
+    temperatures: &mut [Temperature<M>],
+    pair_acceptance: &mut PairAcceptance,
+) {
+    if temperatures.len() < 2 {
+        return;
+    }
+    let num_pairs = temperatures.len() - 1;
+
+    match strategy {
+        ExchangeStrategy::RandomPairs => {
+            for _ in 0..num_pairs {
+                let lower = rng.random_range(0..num_pairs);
+                metropolis_exchange(rng, temperatures, lower, pair_acceptance);
+            }
+        }
+        ExchangeStrategy::EvenOdd => {
+            let start = if even_round { 0 } else { 1 };
+            for lower in (start..num_pairs).step_by(2) {
+                metropolis_exchange(rng, temperatures, lower, pair_acceptance);
+            }
+        }
+        ExchangeStrategy::AnyPairsHeatBath => {
+            let len = temperatures.len();
+            // enough updates such that every pair is chosen about once
+            for _ in 0..len * num_pairs / 2 {
+                let i = rng.random_range(0..len);
+                let mut j = rng.random_range(0..num_pairs);
+                if j >= i {
+                    j += 1;
+                }
+                heat_bath_exchange(rng, temperatures, i.min(j), i.max(j), pair_acceptance);
+            }
+        }
+    }
+    pair_acceptance.count_exchange_try();
+}
+
+/// Attempts to exchange the configurations of temperature `lower` and `lower + 1`
+fn metropolis_exchange<M: EnergyModel>(
+    rng: &mut Pcg64,
+    temperatures: &mut [Temperature<M>],
+    lower: usize,
+    pair_acceptance: &mut PairAcceptance,
+) {
+    let mut iter = temperatures.iter_mut().skip(lower);
+    let a = iter.next().unwrap();
+    let b = iter.next().unwrap();
+    let exchange_prob = exchange_acceptance_probability(a, b);
+    if exchange_prob >= rng.random() {
+        exchange_temperatures(a, b);
+        pair_acceptance.count_acceptance(a.temperature_id, b.temperature_id);
+    } else {
+        a.add_rejected_exchange_to_ringbuffer();
+        b.add_rejected_exchange_to_ringbuffer();
+        pair_acceptance.count_rejected(a.temperature_id, b.temperature_id);
+    }
+}
+
+/// Heat bath exchange of the configurations of temperature `lower` and `upper`.
+/// The result is recorded the same way as by [metropolis_exchange]
+fn heat_bath_exchange<M: EnergyModel>(
+    rng: &mut Pcg64,
+    temperatures: &mut [Temperature<M>],
+    lower: usize,
+    upper: usize,
+    pair_acceptance: &mut PairAcceptance,
+) {
+    let (left, right) = temperatures.split_at_mut(upper);
+    let a = &mut left[lower];
+    let b = &mut right[0];
+    let exchange_prob = (1.0 + (-exchange_log_weight_ratio(a, b)).exp()).recip();
+    let accepted = exchange_prob >= rng.random();
+    if accepted {
+        exchange_temperatures(a, b);
+    } else {
+        a.add_rejected_exchange_to_ringbuffer();
+        b.add_rejected_exchange_to_ringbuffer();
+    }
+    if upper == lower + 1 {
+        if accepted {
+            pair_acceptance.count_acceptance(a.temperature_id, b.temperature_id);
+        } else {
+            pair_acceptance.count_rejected(a.temperature_id, b.temperature_id);
+        }
+    }
+}
+
+fn exchange_temperatures<M: EnergyModel>(a: &mut Temperature<M>, b: &mut Temperature<M>) {
+    a.swap_configuration(b);
+
+    let ea = a.energy_level();
+    let eb = b.energy_level();
+
+    a.hist.increment_quiet(ea);
+    a.ring_buffer.push((a.color, ea));
+    b.hist.increment_quiet(eb);
+    b.ring_buffer.push((b.color, eb));
+}
+
+fn exchange_acceptance_probability<M: EnergyModel>(a: &Temperature<M>, b: &Temperature<M>) -> f64 {
+    1.0_f64.min(exchange_log_weight_ratio(a, b).exp())
+}
+
+/// Logarithm of the ratio of the weights after and before the exchange
+fn exchange_log_weight_ratio<M: EnergyModel>(a: &Temperature<M>, b: &Temperature<M>) -> f64 {
+    assert!(
+        SortHelper {
+            temp: NotNan::new(a.temperature).unwrap()
+        } <= SortHelper {
+            temp: NotNan::new(b.temperature).unwrap()
+        },
+        "{a:?} {b:?}"
+    );
+    //assert!(
+    //    a.temperature <= b.temperature
+    //);
+    let ea = a.energy();
+    let eb = b.energy();
+    (1.0 / a.temperature - 1.0 / b.temperature) * (ea - eb)
+}
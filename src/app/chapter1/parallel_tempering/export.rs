@@ -0,0 +1,120 @@
+use super::{MergeMethod, ParallelTemperingData, ResultingEstimate};
+use sampling::Histogram;
+use std::fmt::Write;
+
+/// Which character separates the columns of the exported tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Separator {
+    #[default]
+    Comma,
+    Tab,
+}
+
+impl Separator {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Comma => ",",
+            Self::Tab => "\t",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Comma => "csv",
+            Self::Tab => "tsv",
+        }
+    }
+}
+
+/// Empty field for NaN, such that spreadsheets and data frames recognize missing values
+fn field(val: f64) -> String {
+    if val.is_nan() {
+        String::new()
+    } else {
+        val.to_string()
+    }
+}
+
+impl ParallelTemperingData {
+    /// One row per energy level: the histograms of all temperatures,
+    /// the log10 of the probability estimated by every temperature,
+    /// the merged estimate and, if known, the analytic probability.
+    ///
+    /// The estimates are the same as the ones shown under 'Resulting Estimate',
+    /// i.e., they include the current z values or the WHAM solution
+    pub fn estimates_table(&mut self, separator: Separator) -> String {
+        let sep = separator.as_str();
+        let temperatures = &self.engine.temperatures;
+        if temperatures.is_empty() {
+            return String::new();
+        }
+        let wham_estimate = match self.merge_method {
+            MergeMethod::Manual => None,
+            MergeMethod::Wham => {
+                let estimate = self.wham.solve(temperatures);
+                self.z = self.wham.z();
+                Some(estimate)
+            }
+        };
+        self.z.resize(temperatures.len(), 0.0);
+        let estimate = ResultingEstimate::calc(self);
+        let merged = wham_estimate.unwrap_or_else(|| estimate.merged(self));
+
+        let temperatures = &self.engine.temperatures;
+        let mut table = format!("level{sep}energy");
+        for temp in temperatures.iter() {
+            let _ = write!(table, "{sep}hist_T={}", temp.temperature);
+        }
+        for temp in temperatures.iter() {
+            let _ = write!(table, "{sep}log10_p_T={}", temp.temperature);
+        }
+        let _ = write!(table, "{sep}merged_log10_p");
+        if self.true_density.is_some() {
+            let _ = write!(table, "{sep}analytic_log10_p");
+        }
+        table.push('\n');
+
+        let bins = merged.len();
+        let energy_factor = ((bins - 1).max(1) as f64).recip();
+        for level in 0..bins {
+            let _ = write!(table, "{level}{sep}{}", level as f64 * energy_factor);
+            for temp in temperatures.iter() {
+                let _ = write!(table, "{sep}{}", temp.hist.hist()[level]);
+            }
+            for pdf in estimate.pdfs.iter() {
+                let _ = write!(table, "{sep}{}", field(pdf[level]));
+            }
+            let _ = write!(table, "{sep}{}", field(merged[level]));
+            if let Some(true_density) = self.true_density.as_ref() {
+                let _ = write!(table, "{sep}{}", field(true_density[level]));
+            }
+            table.push('\n');
+        }
+        table
+    }
+
+    /// One row per pair of adjacent temperatures
+    pub fn exchange_rate_table(&self, separator: Separator) -> String {
+        let sep = separator.as_str();
+        let mut table =
+            format!("lower_temperature{sep}upper_temperature{sep}attempts{sep}exchange_rate\n");
+        for pair in self.engine.temperatures.windows(2) {
+            let counter = self
+                .engine
+                .pair_acceptance
+                .get_pair_acceptance(pair[0].temperature_id, pair[1].temperature_id);
+            let (attempts, rate) = match counter {
+                Some(counter) => (counter.attempts(), counter.acceptance_rate()),
+                None => (0, f64::NAN),
+            };
+            let _ = writeln!(
+                table,
+                "{}{sep}{}{sep}{attempts}{sep}{}",
+                pair[0].temperature,
+                pair[1].temperature,
+                field(rate)
+            );
+        }
+        table
+    }
+}
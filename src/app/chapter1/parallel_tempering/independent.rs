@@ -0,0 +1,119 @@
+use super::{EnergyModel, ParallelTempering, Wham};
+use crate::dark_magic::TimeSliced;
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+use web_time::Instant;
+
+/// # Independent runs of the whole temperature ladder
+///
+/// Used to estimate the statistical error of the merged estimate.
+/// Every run has its own random number generator, seeded from the
+/// random number generator of the original simulation
+pub struct IndependentRuns<M: EnergyModel> {
+    runs: Vec<Run<M>>,
+}
+
+struct Run<M: EnergyModel> {
+    engine: ParallelTempering<M>,
+    wham: Wham,
+    /// Merged estimate of the current histograms, None if they changed since
+    estimate: Option<Vec<f64>>,
+}
+
+/// Mean and standard error of the log10 of the probability of each energy level
+pub struct ErrorEstimate {
+    pub mean: Vec<f64>,
+    /// NaN if fewer than two runs hit the energy level
+    pub std_error: Vec<f64>,
+}
+
+impl<M: EnergyModel> IndependentRuns<M> {
+    /// Creates `count` copies of the temperature ladder of `engine`
+    pub fn new(engine: &mut ParallelTempering<M>, count: usize) -> Self {
+        let runs = (0..count)
+            .map(|_| {
+                let rng = Pcg64::from_rng(&mut engine.rng);
+                Run {
+                    engine: engine.independent_copy(rng),
+                    wham: Wham::default(),
+                    estimate: None,
+                }
+            })
+            .collect();
+        Self { runs }
+    }
+
+    pub fn len(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    /// Number of exchange rounds every run performed
+    pub fn exchange_rounds(&self) -> u64 {
+        self.runs
+            .first()
+            .map_or(0, |run| run.engine.exchange_rounds())
+    }
+
+    /// Merges every run via [Wham] and combines the results.
+    ///
+    /// Only the runs that were simulated since the last call are merged again
+    pub fn estimate(&mut self) -> ErrorEstimate {
+        let estimates: Vec<&Vec<f64>> = self
+            .runs
+            .iter_mut()
+            .filter(|run| !run.engine.temperatures.is_empty())
+            .map(|run| {
+                &*run
+                    .estimate
+                    .get_or_insert_with(|| run.wham.solve(&run.engine.temperatures))
+            })
+            .collect();
+        let bins = estimates.first().map_or(0, |e| e.len());
+
+        let mut mean = Vec::with_capacity(bins);
+        let mut std_error = Vec::with_capacity(bins);
+        for k in 0..bins {
+            let values: Vec<f64> = estimates
+                .iter()
+                .map(|e| e[k])
+                .filter(|v| !v.is_nan())
+                .collect();
+            let n = values.len() as f64;
+            let average = values.iter().sum::<f64>() / n;
+            let error = if values.len() < 2 {
+                f64::NAN
+            } else {
+                let variance =
+                    values.iter().map(|v| (v - average).powi(2)).sum::<f64>() / (n - 1.0);
+                (variance / n).sqrt()
+            };
+            mean.push(average);
+            std_error.push(error);
+        }
+        ErrorEstimate { mean, std_error }
+    }
+}
+
+impl<M: EnergyModel> TimeSliced for IndependentRuns<M> {
+    /// Splits the time budget evenly between the runs.
+    ///
+    /// Returns the steps of all runs combined
+    fn step_budget(&mut self, deadline: Instant) -> u64 {
+        let mut steps = 0;
+        let count = self.runs.len();
+        for (i, run) in self.runs.iter_mut().enumerate() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let run_deadline = Instant::now() + remaining / (count - i) as u32;
+            let run_steps = run.engine.step_budget(run_deadline);
+            if run_steps > 0 {
+                run.estimate = None;
+            }
+            steps += run_steps;
+        }
+        steps
+    }
+}
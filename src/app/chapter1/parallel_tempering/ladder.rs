@@ -0,0 +1,227 @@
+use super::{EnergyModel, PairAcceptance, Temperature};
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU64;
+
+/// Acceptance rates are clamped to this range, as 0 and 1 would lead to infinite or vanishing costs
+const MIN_ACCEPTANCE: f64 = 1e-3;
+const MAX_ACCEPTANCE: f64 = 0.99;
+/// Smallest difference in f(T) that is considered for the feedback optimization
+const MIN_FLOW_DIFFERENCE: f64 = 1e-3;
+/// Only move the inverse temperatures part of the way, otherwise the iteration tends to oscillate
+const DAMPING: f64 = 0.5;
+
+/// What the automatic optimization of the temperatures should aim for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TuneTarget {
+    /// All pairs should have the same exchange rate
+    #[default]
+    ConstantAcceptance,
+    /// Optimize the flow of replicas, i.e., f(T) should decrease linearly.
+    /// See Katzgraber et al., J. Stat. Mech. (2006) P03018
+    FeedbackOptimized,
+}
+
+/// # Automatic optimization of the temperature ladder
+///
+/// Every `sweeps_per_iteration` sweeps the measured statistics
+/// are used to re-space the temperatures between the bottom and the top temperature.
+/// The bottom and top temperatures are never changed.
+/// Negative and positive temperatures are re-spaced separately,
+/// such that no temperature becomes infinite or changes its sign.
+#[derive(Debug, Derivative, Serialize, Deserialize)]
+#[derivative(Default)]
+pub struct LadderTuner {
+    pub enabled: bool,
+    pub target: TuneTarget,
+    #[derivative(Default(value = "NonZeroU64::new(200).unwrap()"))]
+    pub sweeps_per_iteration: NonZeroU64,
+    #[derivative(Default(value = "10"))]
+    pub max_iterations: u32,
+    iterations_done: u32,
+}
+
+impl LadderTuner {
+    pub fn iterations_done(&self) -> u32 {
+        self.iterations_done
+    }
+
+    /// Start counting the iterations from the beginning
+    pub fn restart(&mut self) {
+        self.iterations_done = 0;
+    }
+
+    /// Is it time for the next iteration?
+    pub fn is_due(&self, sweeps: u64) -> bool {
+        self.enabled && sweeps >= self.sweeps_per_iteration.get()
+    }
+
+    /// Re-spaces the temperatures according to the measured statistics.
+    ///
+    /// Returns false if there are not enough statistics yet.
+    /// In that case nothing is changed.
+    pub fn iterate<M: EnergyModel>(
+        &mut self,
+        temperatures: &mut [Temperature<M>],
+        pair_acceptance: &PairAcceptance,
+    ) -> bool {
+        if temperatures.len() < 3 {
+            self.enabled = false;
+            return false;
+        }
+        let betas: Vec<f64> = temperatures.iter().map(|t| t.temperature.recip()).collect();
+        let costs: Option<Vec<f64>> = match self.target {
+            TuneTarget::ConstantAcceptance => temperatures
+                .windows(2)
+                .map(|pair| {
+                    let rate = pair_acceptance
+                        .get_pair_acceptance(pair[0].temperature_id, pair[1].temperature_id)?
+                        .acceptance_rate();
+                    (!rate.is_nan()).then(|| -rate.clamp(MIN_ACCEPTANCE, MAX_ACCEPTANCE).ln())
+                })
+                .collect(),
+            TuneTarget::FeedbackOptimized => temperatures
+                .windows(2)
+                .map(|pair| {
+                    let f_lower = pair[0].up_down.up_fraction();
+                    let f_upper = pair[1].up_down.up_fraction();
+                    (!f_lower.is_nan() && !f_upper.is_nan()).then(|| flow_cost(f_lower, f_upper))
+                })
+                .collect(),
+        };
+        let costs = match costs {
+            Some(costs) => costs,
+            None => return false,
+        };
+
+        let new_betas = respace_by_sign(&betas, &costs);
+        temperatures
+            .iter_mut()
+            .zip(betas.iter().zip(new_betas))
+            .for_each(|(temp, (old, new))| {
+                let beta = old + DAMPING * (new - old);
+                temp.temperature = beta.recip();
+            });
+
+        self.iterations_done += 1;
+        if self.iterations_done >= self.max_iterations {
+            self.enabled = false;
+        }
+        true
+    }
+}
+
+/// The optimal density of temperatures is proportional to sqrt(density * df/dT),
+/// so the number of temperatures an interval should get is proportional to sqrt(df).
+/// This does not depend on how the temperatures are parametrized
+fn flow_cost(f_lower: f64, f_upper: f64) -> f64 {
+    (f_lower - f_upper).max(MIN_FLOW_DIFFERENCE).sqrt()
+}
+
+/// Like [respace], but the negative and the positive `betas` are re-spaced separately.
+/// Thus the betas closest to zero on both sides are kept as well
+/// and the cost of the pair across zero is ignored
+fn respace_by_sign(betas: &[f64], costs: &[f64]) -> Vec<f64> {
+    let split = betas.partition_point(|beta| *beta < 0.0);
+    let mut new_betas = Vec::with_capacity(betas.len());
+    for range in [0..split, split..betas.len()] {
+        let segment = &betas[range.clone()];
+        if segment.len() < 3 {
+            new_betas.extend_from_slice(segment);
+        } else {
+            new_betas.extend(respace(segment, &costs[range.start..range.end - 1]));
+        }
+    }
+    new_betas
+}
+
+/// Moves the inner `betas` such that the cumulative cost between adjacent betas is the same
+/// for all pairs.
+/// The cost is assumed to be distributed uniformly within each interval.
+///
+/// * `betas` have to be sorted ascending
+/// * `costs[i]` is the cost between `betas[i]` and `betas[i+1]`
+fn respace(betas: &[f64], costs: &[f64]) -> Vec<f64> {
+    debug_assert_eq!(betas.len(), costs.len() + 1);
+    let total: f64 = costs.iter().sum();
+    let intervals = costs.len();
+    let mut new_betas = Vec::with_capacity(betas.len());
+    new_betas.push(betas[0]);
+
+    let mut cumulative = 0.0;
+    let mut idx = 0;
+    for k in 1..intervals {
+        let target = total * k as f64 / intervals as f64;
+        while idx < intervals - 1 && cumulative + costs[idx] < target {
+            cumulative += costs[idx];
+            idx += 1;
+        }
+        let fraction = if costs[idx] > 0.0 {
+            ((target - cumulative) / costs[idx]).clamp(0.0, 1.0)
+        } else {
+            0.5
+        };
+        new_betas.push(betas[idx] + fraction * (betas[idx + 1] - betas[idx]));
+    }
+    new_betas.push(betas[intervals]);
+    new_betas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mixed_sign_ladder_keeps_signs() {
+        let betas = [-200.0, -50.0, -20.0, -10.0, 10.0, 20.0, 50.0, 200.0];
+        // very uneven costs, including a huge one across zero
+        let costs = [5.0, 0.1, 0.1, 100.0, 0.1, 0.1, 5.0];
+        let new_betas = respace_by_sign(&betas, &costs);
+        assert_eq!(new_betas.len(), betas.len());
+        // ends and the betas closest to zero are fixed
+        for idx in [0, 3, 4, 7] {
+            assert_eq!(new_betas[idx], betas[idx]);
+        }
+        for (old, new) in betas.iter().zip(new_betas.iter()) {
+            assert_eq!(old.signum(), new.signum());
+            let damped = old + DAMPING * (new - old);
+            assert!(damped != 0.0 && damped.recip().is_finite());
+        }
+        assert!(new_betas.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn feedback_equalizes_flow_differences() {
+        // strongly nonlinear fraction of replicas that move up
+        let f = |beta: f64| 1.0 - ((beta - 1.0) / 9.0).powi(3);
+        let flow_differences = |betas: &[f64]| -> Vec<f64> {
+            betas
+                .windows(2)
+                .map(|pair| f(pair[0]) - f(pair[1]))
+                .collect()
+        };
+        let spread = |betas: &[f64]| {
+            let df = flow_differences(betas);
+            let max = df.iter().copied().fold(f64::MIN, f64::max);
+            let min = df.iter().copied().fold(f64::MAX, f64::min);
+            max / min
+        };
+        let mut betas: Vec<f64> = (0..8).map(|i| 1.0 + 9.0 * i as f64 / 7.0).collect();
+        let initial_spread = spread(&betas);
+        for _ in 0..50 {
+            let costs: Vec<f64> = betas
+                .windows(2)
+                .map(|pair| flow_cost(f(pair[0]), f(pair[1])))
+                .collect();
+            let new_betas = respace_by_sign(&betas, &costs);
+            betas = betas
+                .iter()
+                .zip(new_betas)
+                .map(|(old, new)| old + DAMPING * (new - old))
+                .collect();
+        }
+        let final_spread = spread(&betas);
+        assert!(initial_spread > 10.0, "{initial_spread}");
+        assert!(final_spread < 1.1, "{final_spread}");
+    }
+}
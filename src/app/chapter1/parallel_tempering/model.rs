@@ -0,0 +1,170 @@
+use crate::app::chapter1::coin_sequence_wl::HeadsProbability;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Debug, num::NonZeroU32};
+
+mod coins;
+pub use coins::*;
+mod ising;
+pub use ising::*;
+
+/// # Ensemble, energy and local move of a model that can be simulated via parallel tempering
+///
+/// The energy is measured in discrete levels `0..=max_level`, which are also the bins of the histograms.
+/// The energy that enters the Boltzmann weight is `level / max_level`,
+/// i.e., the weight of a configuration is `exp(-level / (T * max_level))`
+pub trait EnergyModel: Debug + Send + Sized + 'static {
+    /// Everything besides the size that is needed to create a configuration
+    type Parameters: Clone + Default + PartialEq;
+
+    /// Random configuration of `size` elements, drawn uniformly from all configurations
+    fn random(parameters: &Self::Parameters, size: NonZeroU32, rng: &mut Pcg64) -> Self;
+
+    /// Changes the number of elements, keeping as much of the configuration as possible
+    fn resize(&mut self, size: NonZeroU32, rng: &mut Pcg64);
+
+    /// Highest energy level that is possible for the current size
+    fn max_level(&self) -> i32;
+
+    /// Current energy level
+    fn level(&self) -> i32;
+
+    /// Performs a random local change of the configuration and returns the new energy level
+    fn propose(&mut self, rng: &mut Pcg64) -> i32;
+
+    /// Reverts the last change of [EnergyModel::propose]
+    fn undo(&mut self);
+
+    /// Recomputes everything that is derived from the configuration, e.g., after it was loaded from a file.
+    ///
+    /// Returns false if the configuration cannot belong to `parameters`
+    fn refresh(&mut self, parameters: &Self::Parameters) -> bool;
+
+    /// Log10 of the probability of every energy level, if all configurations are equally likely.
+    /// None if it is not known analytically
+    fn analytic_log10_probability(
+        parameters: &Self::Parameters,
+        size: NonZeroU32,
+    ) -> Option<Vec<f64>>;
+}
+
+/// Models that can be chosen in the GUI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ModelKind {
+    /// Energy is the number of heads
+    #[default]
+    Coins,
+    /// Energy is the number of domain walls
+    IsingChain,
+}
+
+impl ModelKind {
+    /// What the elements of the configuration are called
+    pub fn element_name(self) -> &'static str {
+        match self {
+            Self::Coins => "Coins",
+            Self::IsingChain => "Spins",
+        }
+    }
+
+    /// What the energy level is called
+    pub fn level_name(self) -> &'static str {
+        match self {
+            Self::Coins => "Number of Heads",
+            Self::IsingChain => "Domain wall pairs",
+        }
+    }
+
+    /// What the energy, i.e., level / max_level, is called
+    pub fn energy_name(self) -> &'static str {
+        match self {
+            Self::Coins => "Heads rate",
+            Self::IsingChain => "Domain wall density",
+        }
+    }
+}
+
+/// Everything besides the size that is needed to create a configuration of [AnyModel]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ModelParameters {
+    pub kind: ModelKind,
+    /// Only used by [ModelKind::Coins]
+    pub heads_probability: HeadsProbability,
+}
+
+/// Any of the models of [ModelKind], such that the model can be changed at runtime
+#[derive(Debug, Serialize, Deserialize)]
+pub enum AnyModel {
+    Coins(CoinSequence),
+    IsingChain(IsingChain),
+}
+
+impl EnergyModel for AnyModel {
+    type Parameters = ModelParameters;
+
+    fn random(parameters: &ModelParameters, size: NonZeroU32, rng: &mut Pcg64) -> Self {
+        match parameters.kind {
+            ModelKind::Coins => Self::Coins(CoinSequence::random(
+                &parameters.heads_probability,
+                size,
+                rng,
+            )),
+            ModelKind::IsingChain => Self::IsingChain(IsingChain::random(&(), size, rng)),
+        }
+    }
+
+    fn resize(&mut self, size: NonZeroU32, rng: &mut Pcg64) {
+        match self {
+            Self::Coins(model) => model.resize(size, rng),
+            Self::IsingChain(model) => model.resize(size, rng),
+        }
+    }
+
+    fn max_level(&self) -> i32 {
+        match self {
+            Self::Coins(model) => model.max_level(),
+            Self::IsingChain(model) => model.max_level(),
+        }
+    }
+
+    fn level(&self) -> i32 {
+        match self {
+            Self::Coins(model) => model.level(),
+            Self::IsingChain(model) => model.level(),
+        }
+    }
+
+    fn propose(&mut self, rng: &mut Pcg64) -> i32 {
+        match self {
+            Self::Coins(model) => model.propose(rng),
+            Self::IsingChain(model) => model.propose(rng),
+        }
+    }
+
+    fn undo(&mut self) {
+        match self {
+            Self::Coins(model) => model.undo(),
+            Self::IsingChain(model) => model.undo(),
+        }
+    }
+
+    fn refresh(&mut self, parameters: &ModelParameters) -> bool {
+        match (self, parameters.kind) {
+            (Self::Coins(model), ModelKind::Coins) => model.refresh(&parameters.heads_probability),
+            (Self::IsingChain(model), ModelKind::IsingChain) => model.refresh(&()),
+            _ => false,
+        }
+    }
+
+    fn analytic_log10_probability(
+        parameters: &ModelParameters,
+        size: NonZeroU32,
+    ) -> Option<Vec<f64>> {
+        match parameters.kind {
+            ModelKind::Coins => {
+                CoinSequence::analytic_log10_probability(&parameters.heads_probability, size)
+            }
+            ModelKind::IsingChain => IsingChain::analytic_log10_probability(&(), size),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use super::EnergyModel;
+use crate::app::chapter1::coin_sequence_wl::HeadsProbability;
+use crate::app::chapter1::wang_landau::calc_true_log;
+use rand::Rng;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU32;
+
+/// # Sequence of coin flips
+///
+/// The energy level is the number of heads. Every coin shows head with the same probability
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CoinSequence {
+    /// true is heads
+    coins: Vec<bool>,
+    heads: i32,
+    /// Index and previous value of the last changed coin
+    last_change: Option<(usize, bool)>,
+    heads_probability: HeadsProbability,
+}
+
+impl EnergyModel for CoinSequence {
+    type Parameters = HeadsProbability;
+
+    fn random(heads_probability: &HeadsProbability, size: NonZeroU32, rng: &mut Pcg64) -> Self {
+        let p = heads_probability.get();
+        let coins: Vec<bool> = (0..size.get()).map(|_| rng.random_bool(p)).collect();
+        let heads = coins.iter().filter(|&s| *s).count() as i32;
+        Self {
+            coins,
+            heads,
+            last_change: None,
+            heads_probability: *heads_probability,
+        }
+    }
+
+    fn resize(&mut self, size: NonZeroU32, rng: &mut Pcg64) {
+        let size = size.get() as usize;
+        if size <= self.coins.len() {
+            self.coins.truncate(size);
+        } else {
+            let missing = size - self.coins.len();
+            let p = self.heads_probability.get();
+            self.coins.extend((0..missing).map(|_| rng.random_bool(p)));
+        }
+        self.heads = self.coins.iter().filter(|&s| *s).count() as i32;
+        self.last_change = None;
+    }
+
+    fn max_level(&self) -> i32 {
+        self.coins.len() as i32
+    }
+
+    fn level(&self) -> i32 {
+        self.heads
+    }
+
+    /// Throws a random coin again
+    fn propose(&mut self, rng: &mut Pcg64) -> i32 {
+        let idx = rng.random_range(0..self.coins.len());
+        let old_val = self.coins[idx];
+        let new_val = rng.random_bool(self.heads_probability.get());
+        self.coins[idx] = new_val;
+        if old_val != new_val {
+            self.heads += if new_val { 1 } else { -1 };
+        }
+        self.last_change = Some((idx, old_val));
+        self.heads
+    }
+
+    fn undo(&mut self) {
+        if let Some((idx, old_val)) = self.last_change.take() {
+            if self.coins[idx] != old_val {
+                self.heads += if old_val { 1 } else { -1 };
+            }
+            self.coins[idx] = old_val;
+        }
+    }
+
+    fn refresh(&mut self, heads_probability: &HeadsProbability) -> bool {
+        self.heads = self.coins.iter().filter(|&s| *s).count() as i32;
+        self.last_change = None;
+        self.heads_probability = *heads_probability;
+        !self.coins.is_empty()
+    }
+
+    fn analytic_log10_probability(
+        heads_probability: &HeadsProbability,
+        size: NonZeroU32,
+    ) -> Option<Vec<f64>> {
+        Some(calc_true_log(size, *heads_probability))
+    }
+}
@@ -0,0 +1,124 @@
+use super::EnergyModel;
+use rand::Rng;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use statrs::function::factorial::ln_binomial;
+use std::{f64::consts::LOG10_E, num::NonZeroU32};
+
+/// # One dimensional Ising model with periodic boundary conditions
+///
+/// The energy is the number of domain walls, i.e., neighboring spins pointing in
+/// different directions. On a ring the number of domain walls is always even,
+/// so the energy level is the number of pairs of domain walls.
+/// Thus positive temperatures favor ferromagnetic and negative temperatures
+/// antiferromagnetic configurations.
+///
+/// A ring needs at least two spins, so smaller sizes are increased to two
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IsingChain {
+    /// true is spin up
+    spins: Vec<bool>,
+    domain_walls: i32,
+    /// Index of the last flipped spin
+    last_flip: Option<usize>,
+}
+
+impl IsingChain {
+    fn count_domain_walls(&self) -> i32 {
+        let len = self.spins.len();
+        (0..len)
+            .filter(|&i| self.spins[i] != self.spins[(i + 1) % len])
+            .count() as i32
+    }
+
+    /// Number of domain walls next to spin `idx`
+    fn local_domain_walls(&self, idx: usize) -> i32 {
+        let len = self.spins.len();
+        let left = self.spins[(idx + len - 1) % len];
+        let right = self.spins[(idx + 1) % len];
+        (self.spins[idx] != left) as i32 + (self.spins[idx] != right) as i32
+    }
+
+    fn flip(&mut self, idx: usize) {
+        let before = self.local_domain_walls(idx);
+        self.spins[idx] = !self.spins[idx];
+        self.domain_walls += self.local_domain_walls(idx) - before;
+    }
+}
+
+fn ring_size(size: NonZeroU32) -> usize {
+    size.get().max(2) as usize
+}
+
+impl EnergyModel for IsingChain {
+    type Parameters = ();
+
+    fn random(_: &(), size: NonZeroU32, rng: &mut Pcg64) -> Self {
+        let spins = (0..ring_size(size)).map(|_| rng.random_bool(0.5)).collect();
+        let mut chain = Self {
+            spins,
+            domain_walls: 0,
+            last_flip: None,
+        };
+        chain.domain_walls = chain.count_domain_walls();
+        chain
+    }
+
+    fn resize(&mut self, size: NonZeroU32, rng: &mut Pcg64) {
+        let size = ring_size(size);
+        if size <= self.spins.len() {
+            self.spins.truncate(size);
+        } else {
+            let missing = size - self.spins.len();
+            self.spins
+                .extend((0..missing).map(|_| rng.random_bool(0.5)));
+        }
+        self.domain_walls = self.count_domain_walls();
+        self.last_flip = None;
+    }
+
+    fn max_level(&self) -> i32 {
+        self.spins.len() as i32 / 2
+    }
+
+    fn level(&self) -> i32 {
+        self.domain_walls / 2
+    }
+
+    /// Flips a random spin
+    fn propose(&mut self, rng: &mut Pcg64) -> i32 {
+        let idx = rng.random_range(0..self.spins.len());
+        self.flip(idx);
+        self.last_flip = Some(idx);
+        self.level()
+    }
+
+    fn undo(&mut self) {
+        if let Some(idx) = self.last_flip.take() {
+            self.flip(idx);
+        }
+    }
+
+    fn refresh(&mut self, _: &()) -> bool {
+        if self.spins.len() < 2 {
+            return false;
+        }
+        self.domain_walls = self.count_domain_walls();
+        self.last_flip = None;
+        true
+    }
+
+    /// A ring of N spins has 2 * binomial(N, 2k) configurations with 2k domain walls,
+    /// as the positions of the domain walls and the direction of the first spin determine the configuration
+    fn analytic_log10_probability(_: &(), size: NonZeroU32) -> Option<Vec<f64>> {
+        let n = ring_size(size) as u64;
+        let ln_total = n as f64 * std::f64::consts::LN_2;
+        let probabilities = (0..=n / 2)
+            .map(|level| {
+                let ln_count = std::f64::consts::LN_2 + ln_binomial(n, 2 * level);
+                (ln_count - ln_total) * LOG10_E
+            })
+            .collect();
+        Some(probabilities)
+    }
+}
@@ -0,0 +1,103 @@
+use super::Temperature;
+use serde::{Deserialize, Serialize};
+
+/// # Direction of a replica in the temperature ladder
+///
+/// A replica is moving up, if the last end of the ladder it visited was the bottom,
+/// and moving down, if it was the top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ReplicaDirection {
+    Up,
+    Down,
+    /// Did not visit any end of the ladder yet
+    #[default]
+    Unknown,
+}
+
+/// # Persistent identity of a configuration
+///
+/// Travels together with the configuration through the temperature ladder,
+/// in contrast to the `temperature_id`, which stays with the temperature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replica {
+    pub id: u16,
+    pub direction: ReplicaDirection,
+    /// Number of completed round trips bottom -> top -> bottom
+    pub round_trips: u64,
+    /// Only trips that started at the bottom are counted as round trips
+    visited_bottom: bool,
+}
+
+impl Replica {
+    pub fn new(id: u16) -> Self {
+        Self {
+            id,
+            direction: ReplicaDirection::Unknown,
+            round_trips: 0,
+            visited_bottom: false,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.direction = ReplicaDirection::Unknown;
+        self.round_trips = 0;
+        self.visited_bottom = false;
+    }
+}
+
+/// Counts how often a temperature was visited by replicas moving up or down
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct UpDownCounter {
+    up: u64,
+    down: u64,
+}
+
+impl UpDownCounter {
+    pub fn count(&mut self, direction: ReplicaDirection) {
+        match direction {
+            ReplicaDirection::Up => self.up += 1,
+            ReplicaDirection::Down => self.down += 1,
+            ReplicaDirection::Unknown => (),
+        }
+    }
+
+    /// The fraction f(T) of visits by replicas that are moving up.
+    ///
+    /// NaN if no labeled replica visited yet
+    pub fn up_fraction(&self) -> f64 {
+        self.up as f64 / (self.up + self.down) as f64
+    }
+
+    pub fn reset(&mut self) {
+        self.up = 0;
+        self.down = 0;
+    }
+}
+
+/// Updates the directions of the replicas at the ends of the ladder,
+/// counts completed round trips and the up/down visits of every temperature.
+///
+/// Should be called after every round of exchange attempts.
+///
+/// Returns the number of round trips that were completed
+pub fn update_replica_flow<M>(temperatures: &mut [Temperature<M>]) -> u64 {
+    if temperatures.len() < 2 {
+        return 0;
+    }
+    let mut completed = 0;
+    let last = temperatures.len() - 1;
+
+    let bottom = &mut temperatures[0].replica;
+    if bottom.direction == ReplicaDirection::Down && bottom.visited_bottom {
+        bottom.round_trips += 1;
+        completed += 1;
+    }
+    bottom.direction = ReplicaDirection::Up;
+    bottom.visited_bottom = true;
+    temperatures[last].replica.direction = ReplicaDirection::Down;
+
+    for temp in temperatures.iter_mut() {
+        temp.up_down.count(temp.replica.direction);
+    }
+    completed
+}
@@ -0,0 +1,120 @@
+use super::{AnyModel, MergeMethod, ParallelTempering, ParallelTemperingData, Show, COLORS};
+use serde::{Deserialize, Serialize};
+
+/// Has to be increased whenever the format of the session file changes
+pub const SESSION_VERSION: u32 = 2;
+pub const SESSION_EXTENSION: &str = "ron";
+
+/// Which plots are shown
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Visibility {
+    plot: Show,
+    histogram: Show,
+    acceptance: Show,
+    autocorrelation: Show,
+    history: Show,
+    exchange_rate: Show,
+    estimate: Show,
+    replica_flow: Show,
+    z_legend: Show,
+    z: Show,
+}
+
+/// Used for saving, such that nothing has to be cloned
+#[derive(Serialize)]
+struct SessionRef<'a> {
+    version: u32,
+    engine: &'a ParallelTempering<AnyModel>,
+    z: &'a [f64],
+    merge_method: MergeMethod,
+    z_reference: usize,
+    visibility: Visibility,
+}
+
+/// Read before the [Session], such that files of other versions
+/// are reported as such instead of as invalid
+#[derive(Deserialize)]
+struct SessionHeader {
+    version: u32,
+}
+
+/// # Everything that is needed to continue a parallel tempering session
+///
+/// Contains the temperatures, their configurations and statistics,
+/// the state of the random number generator, the z values and which plots are shown
+#[derive(Deserialize)]
+pub struct Session {
+    engine: ParallelTempering<AnyModel>,
+    z: Vec<f64>,
+    merge_method: MergeMethod,
+    z_reference: usize,
+    visibility: Visibility,
+}
+
+impl ParallelTemperingData {
+    /// The session as text in the [ron](https://docs.rs/ron) format
+    pub fn save_session(&self) -> Result<String, ron::Error> {
+        let session = SessionRef {
+            version: SESSION_VERSION,
+            engine: &self.engine,
+            z: &self.z,
+            merge_method: self.merge_method,
+            z_reference: self.z_reference,
+            visibility: Visibility {
+                plot: self.show_plot,
+                histogram: self.show_histogram,
+                acceptance: self.show_acceptance,
+                autocorrelation: self.show_autocorrelation,
+                history: self.show_history,
+                exchange_rate: self.show_exchange_rate,
+                estimate: self.show_estimate,
+                replica_flow: self.show_replica_flow,
+                z_legend: self.z_legend,
+                z: self.show_z,
+            },
+        };
+        ron::ser::to_string_pretty(&session, ron::ser::PrettyConfig::default())
+    }
+
+    /// Replaces the current session. Nothing is changed if the file is invalid
+    pub fn load_session(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let header: SessionHeader = ron::de::from_bytes(bytes).map_err(|e| e.to_string())?;
+        if header.version != SESSION_VERSION {
+            return Err(format!(
+                "Session file has version {}, but only version {} is supported",
+                header.version, SESSION_VERSION
+            ));
+        }
+        let mut session: Session = ron::de::from_bytes(bytes).map_err(|e| e.to_string())?;
+        session.engine.repair_loaded()?;
+        let valid_colors = session.engine.temperatures.iter().all(|temp| {
+            std::iter::once(temp.color)
+                .chain(temp.ring_buffer.iter().map(|(color, _)| *color))
+                .all(|color| (color as usize) < COLORS.len())
+        });
+        if !valid_colors {
+            return Err("Invalid color".to_owned());
+        }
+
+        self.true_density = session.engine.analytic_log10_probability();
+        self.engine = session.engine;
+        self.z = session.z;
+        self.merge_method = session.merge_method;
+        self.z_reference = session.z_reference;
+        let visibility = session.visibility;
+        self.show_plot = visibility.plot;
+        self.show_histogram = visibility.histogram;
+        self.show_acceptance = visibility.acceptance;
+        self.show_autocorrelation = visibility.autocorrelation;
+        self.show_history = visibility.history;
+        self.show_exchange_rate = visibility.exchange_rate;
+        self.show_estimate = visibility.estimate;
+        self.show_replica_flow = visibility.replica_flow;
+        self.z_legend = visibility.z_legend;
+        self.show_z = visibility.z;
+        // belong to the old session
+        self.independent_runs = None;
+        self.wham.restart();
+        Ok(())
+    }
+}
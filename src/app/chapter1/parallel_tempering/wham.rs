@@ -0,0 +1,131 @@
+use super::Temperature;
+use sampling::Histogram;
+use std::f64::consts::LOG10_E;
+
+/// Iteration stops once no free energy changes by more than this
+const TOLERANCE: f64 = 1e-10;
+/// Maximal number of iterations per call of [Wham::solve]
+const MAX_ITERATIONS: usize = 1000;
+
+/// # Multi histogram reweighting
+///
+/// Determines the free energies of all temperatures self-consistently,
+/// see Ferrenberg and Swendsen, Phys. Rev. Lett. 63, 1195 (1989).
+///
+/// The free energies of the last call are used as starting point for the next call,
+/// such that only a few iterations are needed while the simulation runs
+#[derive(Debug, Default)]
+pub struct Wham {
+    /// Free energies, i.e., -ln Z of every temperature
+    free_energies: Vec<f64>,
+    /// Maximal change of the free energies in each iteration of the last call
+    pub history: Vec<f64>,
+}
+
+impl Wham {
+    /// Forget the free energies, i.e., the next call starts from scratch
+    pub fn restart(&mut self) {
+        self.free_energies.clear();
+    }
+
+    pub fn is_converged(&self) -> bool {
+        self.history
+            .last()
+            .is_some_and(|change| *change <= TOLERANCE)
+    }
+
+    /// The z values of the temperatures that make the curves of
+    /// [ResultingEstimate::calc](super::ResultingEstimate::calc) normalized
+    pub fn z(&self) -> Vec<f64> {
+        self.free_energies.iter().map(|f| -f * LOG10_E).collect()
+    }
+
+    /// Returns the log10 of the probability of every energy level, normalized to 1.
+    /// NaN for the energy levels that were never hit
+    pub fn solve<M>(&mut self, temperatures: &[Temperature<M>]) -> Vec<f64> {
+        self.history.clear();
+        if self.free_energies.len() != temperatures.len() {
+            self.free_energies = vec![0.0; temperatures.len()];
+        }
+
+        let bins = temperatures[0].hist.hist().len();
+        let energy_factor = ((bins - 1) as f64).recip();
+        let betas: Vec<f64> = temperatures.iter().map(|t| t.temperature.recip()).collect();
+        let ln_samples: Vec<f64> = temperatures
+            .iter()
+            .map(|t| (t.hist.hist().iter().sum::<usize>() as f64).ln())
+            .collect();
+        let ln_total_hits: Vec<f64> = (0..bins)
+            .map(|k| {
+                let hits: usize = temperatures.iter().map(|t| t.hist.hist()[k]).sum();
+                (hits as f64).ln()
+            })
+            .collect();
+        if ln_total_hits.iter().all(|val| *val == f64::NEG_INFINITY) {
+            return vec![f64::NAN; bins];
+        }
+
+        let mut ln_prob = vec![f64::NEG_INFINITY; bins];
+        let mut exponents = Vec::with_capacity(temperatures.len().max(bins));
+        for _ in 0..MAX_ITERATIONS {
+            for (k, ln_prob) in ln_prob.iter_mut().enumerate() {
+                if ln_total_hits[k] == f64::NEG_INFINITY {
+                    continue;
+                }
+                let energy = k as f64 * energy_factor;
+                exponents.clear();
+                exponents.extend(
+                    ln_samples
+                        .iter()
+                        .zip(self.free_energies.iter())
+                        .zip(betas.iter())
+                        .filter(|((ln_m, _), _)| ln_m.is_finite())
+                        .map(|((ln_m, f), beta)| ln_m + f - beta * energy),
+                );
+                *ln_prob = ln_total_hits[k] - log_sum_exp(&exponents);
+            }
+            let norm = log_sum_exp(&ln_prob);
+            ln_prob.iter_mut().for_each(|val| *val -= norm);
+
+            let mut max_change: f64 = 0.0;
+            for (f, beta) in self.free_energies.iter_mut().zip(betas.iter()) {
+                exponents.clear();
+                exponents.extend(
+                    ln_prob
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, ln_p)| ln_p.is_finite())
+                        .map(|(k, ln_p)| ln_p - beta * k as f64 * energy_factor),
+                );
+                let new_f = -log_sum_exp(&exponents);
+                max_change = max_change.max((new_f - *f).abs());
+                *f = new_f;
+            }
+            self.history.push(max_change);
+            if max_change <= TOLERANCE {
+                break;
+            }
+        }
+
+        ln_prob
+            .into_iter()
+            .map(|val| {
+                if val.is_finite() {
+                    val * LOG10_E
+                } else {
+                    f64::NAN
+                }
+            })
+            .collect()
+    }
+}
+
+/// ln(sum(exp(values))) without overflow
+fn log_sum_exp(values: &[f64]) -> f64 {
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    let sum: f64 = values.iter().map(|val| (val - max).exp()).sum();
+    max + sum.ln()
+}
@@ -0,0 +1,37 @@
+use std::f64::consts::LN_10;
+
+/// Number of points used to draw [binomial_rate_function]
+const REFERENCE_POINTS: usize = 401;
+
+/// Converts log10 of a probability into the large deviation rate function
+/// Φ = -(1/n) ln P, where `n` is the system size. NaN stays NaN
+pub fn log10_to_rate(log10_probability: f64, n: u32) -> f64 {
+    -LN_10 * log10_probability / n as f64
+}
+
+/// Converts every entry of a log10 probability into the rate function, see [log10_to_rate]
+pub fn log10_slice_to_rate(log10_probability: &mut [f64], n: u32) {
+    log10_probability
+        .iter_mut()
+        .for_each(|val| *val = log10_to_rate(*val, n));
+}
+
+/// Rate function of the heads rate `x` of coins that show head with probability `p`,
+/// i.e., the Kullback–Leibler divergence of `x` from `p`.
+/// Finite size results converge to it for n → ∞
+pub fn binomial_rate_function(x: f64, p: f64) -> f64 {
+    // 0 ln 0 = 0
+    let term = |a: f64, b: f64| if a == 0.0 { 0.0 } else { a * (a / b).ln() };
+    term(x, p) + term(1.0 - x, 1.0 - p)
+}
+
+/// Points of [binomial_rate_function] for heads rates from 0 to 1
+pub fn binomial_rate_points(p: f64) -> Vec<[f64; 2]> {
+    let factor = ((REFERENCE_POINTS - 1) as f64).recip();
+    (0..REFERENCE_POINTS)
+        .map(|i| {
+            let x = i as f64 * factor;
+            [x, binomial_rate_function(x, p)]
+        })
+        .collect()
+}
@@ -1,22 +1,47 @@
 use super::coin_sequence_wl::*;
 use super::parallel_tempering::SidePanelView;
-use crate::dark_magic::BoxedAnything;
+use super::rate_function::{binomial_rate_points, log10_to_rate};
+use crate::dark_magic::{BoxedAnything, Scheduler, TimeSliced};
 use crate::misc::*;
 use derivative::Derivative;
 use egui::{Button, CentralPanel, DragValue, Slider};
 use egui_plot::{Legend, Line, Plot, PlotPoints, Points};
-use rand::{distr::Uniform, prelude::Distribution, SeedableRng};
+use rand::SeedableRng;
 use rand_pcg::Pcg64;
 use sampling::WangLandau1T;
-use sampling::{HistU32Fast, Histogram, WangLandau, WangLandauEnergy};
-use statrs::distribution::{Binomial, Discrete};
-use std::f64::consts::LOG10_E;
+use sampling::{HistU32Fast, Histogram};
+use serde::{Deserialize, Serialize};
 use std::{
     num::{NonZeroU32, NonZeroUsize},
     time::Duration,
 };
 use web_time::Instant;
 
+mod classic;
+pub use classic::*;
+mod convergence;
+pub use convergence::*;
+mod observable;
+pub use observable::*;
+mod windows;
+pub use windows::*;
+
+/// Upper limit for the number of coins that can be chosen
+const MAX_COINS: u32 = 10000;
+/// Upper limit for the number of energy windows that can be chosen
+const MAX_WINDOWS: u32 = 16;
+
+/// Plots below the estimate of the probability
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum LowerPlot {
+    Hidden,
+    /// log f and error of the estimate, also of the last simulation with the other refinement
+    #[default]
+    Convergence,
+    /// Flatness of the histogram, refinement stages and error of the estimate
+    Diagnostics,
+}
+
 type ThisWl = WangLandau1T<
     sampling::HistogramFast<u32>,
     rand_pcg::Lcg128Xsl64,
@@ -32,6 +57,9 @@ pub struct WangLandauConfig {
     /// How many coins to consider
     #[derivative(Default(value = "NonZeroU32::new(500).unwrap()"))]
     coin_sequence_length: NonZeroU32,
+    /// Property of the coin flip sequence whose distribution is estimated
+    observable: CoinObservable,
+    heads_probability: HeadsProbability,
     /// Seed for random number generator
     seed: u64,
     /// Contains Wang landau and true density
@@ -40,6 +68,18 @@ pub struct WangLandauConfig {
     side_panel: SidePanelView,
     #[derivative(Default(value = "0.00001"))]
     target_log_f: f64,
+    /// Number of energy windows, each with its own Wang Landau simulation
+    #[derivative(Default(value = "NonZeroU32::new(1).unwrap()"))]
+    windows: NonZeroU32,
+    /// Number of bins neighbouring windows have in common
+    #[derivative(Default(value = "10"))]
+    overlap: u32,
+    refinement: Refinement,
+    /// History of the last simulation that used the other refinement, for comparison,
+    /// together with the parameters of that simulation
+    other_history: Option<(WangLandauParameters, ConvergenceHistory)>,
+    /// What is shown below the estimate
+    lower_plot: LowerPlot,
     /// Log or Linear?
     display: DisplayState,
     #[derivative(Default(value = "LineOrPoints::Line"))]
@@ -49,6 +89,186 @@ pub struct WangLandauConfig {
     slow_motion: Speed,
     #[derivative(Default(value = "NonZeroUsize::new(512).unwrap()"))]
     slow_motion_speed: NonZeroUsize,
+    scheduler: Scheduler,
+}
+
+/// The parameters of the page that are worth remembering
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WangLandauParameters {
+    pub coin_sequence_length: NonZeroU32,
+    pub observable: CoinObservable,
+    pub heads_probability: HeadsProbability,
+    pub seed: u64,
+    pub target_log_f: f64,
+    pub windows: NonZeroU32,
+    pub overlap: u32,
+    pub refinement: Refinement,
+}
+
+impl Default for WangLandauParameters {
+    fn default() -> Self {
+        WangLandauConfig::default().parameters()
+    }
+}
+
+impl WangLandauParameters {
+    /// Encodes the parameters for a link, see [crate::app::split_link]
+    pub fn to_query(&self) -> String {
+        format!(
+            "coins={}&p={}&observable={}&seed={}&logf={:e}&windows={}&overlap={}&refinement={}",
+            self.coin_sequence_length,
+            self.heads_probability.get(),
+            match self.observable {
+                CoinObservable::HeadCount => "heads".to_owned(),
+                CoinObservable::LongestHeadRun => "run".to_owned(),
+                CoinObservable::Changes => "changes".to_owned(),
+                CoinObservable::WeightedSum { period } => format!("weighted&period={period}"),
+            },
+            self.seed,
+            self.target_log_f,
+            self.windows,
+            self.overlap,
+            match self.refinement {
+                Refinement::OneOverT => "1t",
+                Refinement::Classic => "classic",
+            }
+        )
+    }
+
+    /// Parameters that are missing or invalid keep their current value
+    pub fn update_from_query(&mut self, query: &[(&str, &str)]) {
+        let mut period = None;
+        for (key, value) in query {
+            match *key {
+                "coins" => {
+                    if let Ok(coins) = value.parse::<NonZeroU32>() {
+                        if coins.get() <= MAX_COINS {
+                            self.coin_sequence_length = coins;
+                        }
+                    }
+                }
+                "p" => {
+                    if let Some(p) = value.parse().ok().and_then(HeadsProbability::new) {
+                        self.heads_probability = p;
+                    }
+                }
+                "seed" => {
+                    if let Ok(seed) = value.parse() {
+                        self.seed = seed;
+                    }
+                }
+                "logf" => {
+                    if let Ok(log_f) = value.parse::<f64>() {
+                        if log_f > 0.0 && log_f.is_finite() {
+                            self.target_log_f = log_f;
+                        }
+                    }
+                }
+                "windows" => {
+                    if let Ok(windows) = value.parse::<NonZeroU32>() {
+                        if windows.get() <= MAX_WINDOWS {
+                            self.windows = windows;
+                        }
+                    }
+                }
+                "overlap" => {
+                    if let Ok(overlap) = value.parse::<u32>() {
+                        if overlap >= 1 {
+                            self.overlap = overlap;
+                        }
+                    }
+                }
+                "refinement" => match *value {
+                    "1t" => self.refinement = Refinement::OneOverT,
+                    "classic" => self.refinement = Refinement::Classic,
+                    _ => (),
+                },
+                "observable" => match *value {
+                    "heads" => self.observable = CoinObservable::HeadCount,
+                    "run" => self.observable = CoinObservable::LongestHeadRun,
+                    "changes" => self.observable = CoinObservable::Changes,
+                    "weighted"
+                        if !matches!(self.observable, CoinObservable::WeightedSum { .. }) =>
+                    {
+                        self.observable = CoinObservable::default_weighted_sum();
+                    }
+                    _ => (),
+                },
+                "period" => {
+                    if let Ok(value) = value.parse::<NonZeroU32>() {
+                        if value.get() <= MAX_WEIGHT_PERIOD {
+                            period = Some(value);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        // the period can come before the observable
+        if let (CoinObservable::WeightedSum { period: current }, Some(period)) =
+            (&mut self.observable, period)
+        {
+            *current = period;
+        }
+    }
+}
+
+impl WangLandauConfig {
+    pub fn parameters(&self) -> WangLandauParameters {
+        WangLandauParameters {
+            coin_sequence_length: self.coin_sequence_length,
+            observable: self.observable,
+            heads_probability: self.heads_probability,
+            seed: self.seed,
+            target_log_f: self.target_log_f,
+            windows: self.windows,
+            overlap: self.overlap,
+            refinement: self.refinement,
+        }
+    }
+
+    /// A running simulation is replaced by a new one using the parameters
+    pub fn apply_parameters(&mut self, parameters: WangLandauParameters) {
+        self.coin_sequence_length = parameters.coin_sequence_length;
+        self.observable = parameters.observable;
+        self.heads_probability = parameters.heads_probability;
+        self.seed = parameters.seed;
+        self.target_log_f = parameters.target_log_f;
+        self.windows = parameters.windows;
+        self.overlap = parameters.overlap;
+        self.refinement = parameters.refinement;
+        if self.simulation.is_some() {
+            self.start_simulation();
+        }
+    }
+
+    /// Replaces the simulation, if any, by a new one.
+    /// If only the refinement changed, the history of the old simulation is kept for comparison.
+    /// The kept history is dropped as soon as anything else changes
+    pub fn start_simulation(&mut self) {
+        let parameters = self.parameters();
+        // equal besides the refinement
+        let same_system = |other: &WangLandauParameters| {
+            WangLandauParameters {
+                refinement: parameters.refinement,
+                ..other.clone()
+            } == parameters
+        };
+        let new = Simulation::new(self);
+        if let Some(old) = self.simulation.replace(new) {
+            if old.parameters.refinement != parameters.refinement && same_system(&old.parameters) {
+                self.other_history = Some((old.parameters, old.history));
+            }
+        }
+        if !self
+            .other_history
+            .as_ref()
+            .is_some_and(|(other, _)| same_system(other))
+        {
+            self.other_history = None;
+        }
+    }
 }
 
 pub fn wang_landau_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
@@ -75,6 +295,8 @@ pub fn wang_landau_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
                     ui.label("Display mode:");
                     ui.radio_value(&mut data.display, DisplayState::Linear, "Linear");
                     ui.radio_value(&mut data.display, DisplayState::Log, "Logarithmic");
+                    ui.radio_value(&mut data.display, DisplayState::RateFunction, "Rate function")
+                        .on_hover_text("Φ = -(1/n) ln P as function of the observable divided by the number of coins n");
                 });
 
                 ui.horizontal(|ui| {
@@ -92,20 +314,94 @@ pub fn wang_landau_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
                     });
                 }
 
+                ui.horizontal(|ui| {
+                    let old = data.observable;
+                    ui.label("Observable:");
+                    egui::ComboBox::from_id_salt("wl_observable")
+                        .selected_text(data.observable.name())
+                        .show_ui(ui, |ui| {
+                            let weighted_sum = match data.observable {
+                                CoinObservable::WeightedSum { .. } => data.observable,
+                                _ => CoinObservable::default_weighted_sum(),
+                            };
+                            for observable in [
+                                CoinObservable::HeadCount,
+                                CoinObservable::LongestHeadRun,
+                                CoinObservable::Changes,
+                                weighted_sum,
+                            ] {
+                                ui.selectable_value(
+                                    &mut data.observable,
+                                    observable,
+                                    observable.name(),
+                                );
+                            }
+                        });
+                    if let CoinObservable::WeightedSum { period } = &mut data.observable {
+                        ui.label("Period");
+                        ui.add(DragValue::new(period).range(1..=MAX_WEIGHT_PERIOD))
+                            .on_hover_text("The heads are weighted with 1, 2, ..., period, 1, 2, ... in the order of the coins");
+                    }
+                    if old != data.observable && data.simulation.is_some() {
+                        data.start_simulation();
+                    }
+                });
+                if !data
+                    .observable
+                    .has_exact_result(data.coin_sequence_length, data.heads_probability)
+                {
+                    ui.label(format!(
+                        "No exact result for more than {} coins",
+                        max_exact_coins(data.heads_probability)
+                    ));
+                }
+
                 ui.horizontal(|ui| {
                     let old = data.coin_sequence_length;
                     ui.label("Number of coins");
-                    ui.add(DragValue::new(&mut data.coin_sequence_length).range(1..=10000));
+                    ui.add(DragValue::new(&mut data.coin_sequence_length).range(1..=MAX_COINS));
                     if old != data.coin_sequence_length && data.simulation.is_some() {
-                        let sim = Simulation::new(data);
-                        data.simulation = Some(sim);
+                        data.start_simulation();
+                    }
+                });
+
+                if heads_probability_slider(ui, &mut data.heads_probability)
+                    && data.simulation.is_some()
+                {
+                    data.start_simulation();
+                }
+
+                ui.horizontal(|ui| {
+                    let old = (data.windows, data.overlap);
+                    ui.label("Windows");
+                    ui.add(DragValue::new(&mut data.windows).range(1..=MAX_WINDOWS))
+                        .on_hover_text("Split the range of the observable into overlapping windows, each with its own Wang Landau simulation");
+                    if data.windows.get() > 1 {
+                        ui.label("Overlap");
+                        ui.add(DragValue::new(&mut data.overlap).range(1..=MAX_COINS))
+                            .on_hover_text("Number of bins that neighbouring windows have in common. The estimates of the windows are matched in there");
+                    }
+                    if old != (data.windows, data.overlap) && data.simulation.is_some() {
+                        data.start_simulation();
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    let old = data.refinement;
+                    ui.label("Refinement:");
+                    ui.radio_value(&mut data.refinement, Refinement::OneOverT, "1/t")
+                        .on_hover_text("Halve log f until it is smaller than 1/t, afterwards log f = 1/t, where t is the number of steps per bin");
+                    ui.radio_value(&mut data.refinement, Refinement::Classic, "Classic")
+                        .on_hover_text("Halve log f whenever the histogram is flat. The error of the estimate saturates, as log f decreases too fast");
+                    if old != data.refinement && data.simulation.is_some() {
+                        data.start_simulation();
                     }
                 });
 
                 match data.simulation.as_ref() {
                     None => {
                         if ui.add(Button::new("Create Simulation")).clicked() {
-                            data.simulation = Some(Simulation::new(data));
+                            data.start_simulation();
                         }
                         ui.horizontal(|ui| {
                             ui.label("Rng Seed:");
@@ -127,17 +423,42 @@ pub fn wang_landau_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
                     );
                     if old_target != data.target_log_f {
                         if let Some(sim) = data.simulation.as_mut() {
-                            sim.wl.set_log_f_threshold(data.target_log_f).unwrap();
+                            sim.set_log_f_threshold(data.target_log_f);
                         }
+                        // the comparison only makes sense for the same target
+                        data.other_history = None;
                     }
                 });
                 if let Some(sim) = data.simulation.as_ref() {
-                    ui.label(format!("Current log f: {:e}", sim.wl.log_f()));
-                    ui.label(format!("Steps: {:e}", sim.wl.step_counter()));
+                    ui.label(format!("Current log f: {:e}", sim.log_f()))
+                        .on_hover_text("Largest log f of all windows");
+                    ui.label(format!("Steps: {:e}", sim.step_counter()));
+                    ui.label(format!(
+                        "Steps per second: {:.3e}",
+                        data.scheduler.steps_per_second(0)
+                    ));
 
                     line_or_points_radio_btn(ui, &mut data.analytic, "Analytic:");
                     line_or_points_radio_btn(ui, &mut data.simple_sample, "Simple Sample:");
                     line_or_points_radio_btn(ui, &mut data.wang_landau, "Wang Landau:");
+                    ui.horizontal(|ui| {
+                        ui.label("Lower plot:");
+                        ui.radio_value(&mut data.lower_plot, LowerPlot::Hidden, "Hidden");
+                        ui.radio_value(
+                            &mut data.lower_plot,
+                            LowerPlot::Convergence,
+                            "Convergence",
+                        );
+                        ui.radio_value(
+                            &mut data.lower_plot,
+                            LowerPlot::Diagnostics,
+                            "Diagnostics",
+                        )
+                        .on_hover_text(
+                            "Flatness of the histogram, refinement stages passed \
+                            and error of the estimate, all recorded after every frame",
+                        );
+                    });
                 }
             });
         }
@@ -152,63 +473,160 @@ pub fn wang_landau_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
 
     if let Some(sim) = data.simulation.as_mut() {
         CentralPanel::default().show(ctx, |ui| {
-            let mut estimate = sim.wl.log_density_base10();
+            let (mut estimate, mut window_estimates) =
+                merge_windows(&sim.windows, sim.simple_sample_hist.bin_count());
 
-            sampling::norm_log10_sum_to_1(&mut estimate);
-
-            if data.display == DisplayState::Linear {
-                estimate.iter_mut().for_each(|val| {
-                    *val = 10.0_f64.powf(*val);
-                });
+            let coins = sim.coins();
+            let x_scale = data.display.x_scale(coins);
+            if data.display != DisplayState::Log {
+                estimate
+                    .iter_mut()
+                    .chain(window_estimates.iter_mut().flatten())
+                    .for_each(|val| {
+                        *val = data.display.convert_log10(*val, coins);
+                    });
             }
 
             let wang_landau_estimate =
-                slice_to_line_or_points(&estimate, "Wang Landau", data.wang_landau);
+                slice_to_line_or_points(&estimate, x_scale, "Wang Landau", data.wang_landau);
+
+            // a single window is identical to the merged estimate
+            let window_plots: Vec<_> = if sim.windows.len() > 1 {
+                sim.windows
+                    .iter()
+                    .zip(window_estimates.iter())
+                    .enumerate()
+                    .map(|(i, (window, window_estimate))| {
+                        let points: PlotPoints = window_estimate
+                            .iter()
+                            .enumerate()
+                            .map(|(k, val)| [(window.left as usize + k) as f64 * x_scale, *val])
+                            .collect();
+                        Line::new(format!("Window {i}"), points)
+                            // colors 4 to 10 are not used by anything else in the plot
+                            .color(super::parallel_tempering::get_color(
+                                (4 + i % 7) as u8,
+                                is_dark_mode,
+                            ))
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
 
             let current_energy_wl_point = match data.slow_motion {
                 Speed::Regular => None,
-                Speed::SlowMotion => sim.wl.energy().map(|energy| {
-                    Points::new(
-                        "Current WL walker",
-                        PlotPoints::new(vec![[*energy as f64, estimate[*energy as usize]]]),
+                Speed::SlowMotion => {
+                    let points: Vec<[f64; 2]> = sim
+                        .windows
+                        .iter()
+                        .filter_map(|window| window.energy())
+                        .map(|energy| [energy as f64 * x_scale, estimate[energy as usize]])
+                        .collect();
+                    Some(
+                        Points::new("Current WL walker", PlotPoints::new(points))
+                            .radius(13.)
+                            .shape(egui_plot::MarkerShape::Cross)
+                            .color(super::parallel_tempering::get_color(3, is_dark_mode)),
                     )
-                    .radius(13.)
-                    .shape(egui_plot::MarkerShape::Cross)
-                    .color(super::parallel_tempering::get_color(3, is_dark_mode))
-                }),
+                }
             };
 
-            let true_density = match data.display {
-                DisplayState::Linear => sim.true_density_lin.as_slice(),
-                DisplayState::Log => &sim.true_density_log,
-            };
+            let analytic_results = sim.true_density_log.as_ref().map(|density| {
+                let density: Vec<f64> = density
+                    .iter()
+                    .map(|val| data.display.convert_log10(*val, coins))
+                    .collect();
+                slice_to_line_or_points(&density, x_scale, "Analytic", data.analytic)
+            });
 
-            let analytic_results = slice_to_line_or_points(true_density, "Analytic", data.analytic);
+            // the limit n → ∞ is only known for the head count
+            let rate_function_limit = (data.display == DisplayState::RateFunction
+                && sim.observable == CoinObservable::HeadCount)
+                .then(|| {
+                    Line::new(
+                        "Rate function for n → ∞",
+                        binomial_rate_points(sim.heads_probability.get()),
+                    )
+                    .style(egui_plot::LineStyle::dashed_loose())
+                });
 
             let simple_estimate = sim.get_simple_sample_estimate(data.display);
-            let simple_plot =
-                slice_to_line_or_points(&simple_estimate, "Simple Sampling", data.simple_sample);
+            let simple_plot = slice_to_line_or_points(
+                &simple_estimate,
+                x_scale,
+                "Simple Sampling",
+                data.simple_sample,
+            );
 
             let y_label = get_rich_text_size(data.display.get_y_label(), 15.0);
-            let x_label = get_rich_text_size("Number of Heads", 15.0);
+            let x_label = match data.display {
+                DisplayState::RateFunction => sim.observable.rate_axis_label(),
+                _ => sim.observable.axis_label(),
+            };
+            let x_label = get_rich_text_size(x_label, 15.0);
 
             let legend = Legend::default().text_style(egui::TextStyle::Heading);
 
-            Plot::new("Wl_plot_HASH")
+            let convergence_height = ui.available_height() * 0.35;
+            let mut main_plot = Plot::new("Wl_plot_HASH");
+            if data.lower_plot != LowerPlot::Hidden {
+                main_plot = main_plot.height(ui.available_height() - convergence_height);
+            }
+            main_plot
                 .y_axis_label(y_label)
                 .x_axis_label(x_label)
                 .legend(legend)
                 .show(ui, |plot_ui| {
                     wang_landau_estimate.plot(plot_ui);
-                    analytic_results.plot(plot_ui);
+                    for line in window_plots {
+                        plot_ui.line(line);
+                    }
+                    if let Some(analytic_results) = analytic_results {
+                        analytic_results.plot(plot_ui);
+                    }
+                    if let Some(line) = rate_function_limit {
+                        plot_ui.line(line);
+                    }
                     simple_plot.plot(plot_ui);
                     if let Some(point) = current_energy_wl_point {
                         plot_ui.points(point);
                     }
                 });
+
+            if data.lower_plot == LowerPlot::Convergence {
+                let mut lines = Vec::from(convergence_lines(
+                    &sim.history,
+                    sim.parameters.refinement,
+                    is_dark_mode,
+                ));
+                if let Some((parameters, history)) = data.other_history.as_ref() {
+                    lines.extend(convergence_lines(
+                        history,
+                        parameters.refinement,
+                        is_dark_mode,
+                    ));
+                }
+                Plot::new("Wl_convergence_HASH")
+                    .height(convergence_height)
+                    .x_axis_label(get_rich_text_size("log10 of steps", 15.0))
+                    .y_axis_label(get_rich_text_size("log10", 15.0))
+                    .legend(Legend::default())
+                    .show(ui, |plot_ui| {
+                        for line in lines {
+                            plot_ui.line(line);
+                        }
+                    });
+            } else if data.lower_plot == LowerPlot::Diagnostics {
+                diagnostics_plots(ui, &sim.history, convergence_height, is_dark_mode);
+            }
         });
 
-        sim.sample(data.slow_motion, data.slow_motion_speed);
+        let max_steps = data
+            .slow_motion
+            .is_slow_motion()
+            .then_some(data.slow_motion_speed);
+        data.scheduler.run(ctx, max_steps, &mut [sim]);
 
         match data.slow_motion {
             Speed::Regular => {
@@ -221,95 +639,248 @@ pub fn wang_landau_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
     }
 }
 
-pub fn calc_true_log(coin_sequence_length: NonZeroU32) -> Vec<f64> {
-    let binomial = Binomial::new(0.5, coin_sequence_length.get() as u64).unwrap();
-    (0..=coin_sequence_length.get() as u64)
-        .map(|k| LOG10_E * binomial.ln_pmf(k))
-        .collect()
+/// log f and the error of the estimate as function of the steps, both in log10
+fn convergence_lines<'a>(
+    history: &ConvergenceHistory,
+    refinement: Refinement,
+    is_dark_mode: bool,
+) -> [Line<'a>; 2] {
+    let color_idx = match refinement {
+        Refinement::OneOverT => 1,
+        Refinement::Classic => 2,
+    };
+    let color = super::parallel_tempering::get_color(color_idx, is_dark_mode);
+    let points = |value: fn(&ConvergencePoint) -> f64| -> PlotPoints {
+        history
+            .points()
+            .iter()
+            .filter(|point| point.steps > 0 && value(point) > 0.0)
+            .map(|point| [(point.steps as f64).log10(), value(point).log10()])
+            .collect()
+    };
+    [
+        Line::new(
+            format!("log f ({})", refinement.name()),
+            points(|point| point.log_f),
+        )
+        .color(color),
+        Line::new(
+            format!("error ({})", refinement.name()),
+            points(|point| point.max_error),
+        )
+        .color(color)
+        .style(egui_plot::LineStyle::dashed_loose()),
+    ]
+}
+
+/// Flatness, refinement stages and error of the estimate as function of the log10 of the steps,
+/// next to each other
+fn diagnostics_plots(
+    ui: &mut egui::Ui,
+    history: &ConvergenceHistory,
+    height: f32,
+    is_dark_mode: bool,
+) {
+    let points = |value: &dyn Fn(&ConvergencePoint) -> f64| -> PlotPoints {
+        history
+            .points()
+            .iter()
+            .filter(|point| point.steps > 0)
+            .map(|point| [(point.steps as f64).log10(), value(point)])
+            .collect()
+    };
+    let color = |idx| super::parallel_tempering::get_color(idx, is_dark_mode);
+    let plots = [
+        (
+            "Wl_flatness_HASH",
+            "min/mean of histogram",
+            vec![Line::new("flatness", points(&|point| point.flatness)).color(color(0))],
+        ),
+        (
+            "Wl_stages_HASH",
+            "refinement stages",
+            vec![Line::new("stages", points(&|point| point.stages as f64)).color(color(1))],
+        ),
+        (
+            "Wl_error_HASH",
+            "log10 of error",
+            vec![
+                Line::new("max error", points(&|point| point.max_error.log10())).color(color(2)),
+                Line::new("mean error", points(&|point| point.mean_error.log10())).color(color(3)),
+            ],
+        ),
+    ];
+    ui.columns(plots.len(), |columns| {
+        for (ui, (id, y_label, lines)) in columns.iter_mut().zip(plots) {
+            Plot::new(id)
+                .height(height)
+                .link_axis("Wl_diagnostics_HASH", [true, false])
+                .x_axis_label(get_rich_text_size("log10 of steps", 15.0))
+                .y_axis_label(get_rich_text_size(y_label, 15.0))
+                .legend(Legend::default())
+                .show(ui, |plot_ui| {
+                    for line in lines {
+                        plot_ui.line(line);
+                    }
+                });
+        }
+    });
+}
+
+/// Log10 of the probability of the number of heads
+pub fn calc_true_log(
+    coin_sequence_length: NonZeroU32,
+    heads_probability: HeadsProbability,
+) -> Vec<f64> {
+    CoinObservable::HeadCount
+        .exact_log10(coin_sequence_length, heads_probability)
+        .expect("The head count has a closed form")
 }
 
 #[derive(Debug)]
 pub struct Simulation {
     rng: Pcg64,
-    true_density_log: Vec<f64>,
-    true_density_lin: Vec<f64>,
+    observable: CoinObservable,
+    heads_probability: HeadsProbability,
+    /// None if there is no exact result for the observable
+    true_density_log: Option<Vec<f64>>,
+    /// Contains a bin for every value of the observable
     simple_sample_hist: HistU32Fast,
-    wl: ThisWl,
+    /// Reused for every sequence of simple sampling
+    simple_sample_flips: Vec<CoinFlip>,
+    /// Ordered by the value of the observable
+    windows: Vec<Window>,
+    /// Parameters the simulation was created with, the target log f is kept up to date
+    parameters: WangLandauParameters,
+    history: ConvergenceHistory,
 }
 
 impl Simulation {
     pub fn new(data: &WangLandauConfig) -> Self {
         let mut rng = Pcg64::seed_from_u64(data.seed);
-        let wl_rng = Pcg64::from_rng(&mut rng);
-        let coin_rng = Pcg64::from_rng(&mut rng);
-
-        let ensemble = CoinFlipSequence::new(data.coin_sequence_length.get() as usize, coin_rng);
-
-        let histogram = HistU32Fast::new_inclusive(0, data.coin_sequence_length.get()).unwrap();
-
-        let mut wl = WangLandau1T::new(
-            data.target_log_f,
-            ensemble,
-            wl_rng,
-            1,
-            histogram,
-            data.coin_sequence_length.get() as usize * 10,
-        )
-        .unwrap();
-
-        // Wl needs to be initialized
-        wl.init_greedy_heuristic(energy_fn, None).unwrap();
-
-        let true_density_log = calc_true_log(data.coin_sequence_length);
-        let true_density_lin = true_density_log
-            .iter()
-            .map(|val| 10.0_f64.powf(*val))
+        let coins = data.coin_sequence_length.get();
+        let (min_value, max_value) = data.observable.sampled_range(coins, data.heads_probability);
+        let windows = window_ranges(min_value, max_value, data.windows.get(), data.overlap)
+            .into_iter()
+            .map(|(left, right)| {
+                let coin_rng = Pcg64::from_rng(&mut rng);
+                let ensemble =
+                    CoinFlipSequence::new(coins as usize, data.heads_probability, coin_rng);
+                Window::new(
+                    left,
+                    right,
+                    ensemble,
+                    data.target_log_f,
+                    data.refinement,
+                    data.observable,
+                    &mut rng,
+                )
+            })
             .collect();
 
+        let true_density_log = data
+            .observable
+            .exact_log10(data.coin_sequence_length, data.heads_probability);
+
         Simulation {
+            observable: data.observable,
+            heads_probability: data.heads_probability,
             true_density_log,
-            true_density_lin,
-            simple_sample_hist: HistU32Fast::new_inclusive(0, data.coin_sequence_length.get())
-                .unwrap(),
+            simple_sample_hist: HistU32Fast::new_inclusive(0, max_value).unwrap(),
+            simple_sample_flips: vec![CoinFlip::Tail; coins as usize],
             rng,
-            wl,
+            windows,
+            parameters: data.parameters(),
+            history: ConvergenceHistory::default(),
         }
     }
 
-    pub fn sample(&mut self, slow_motion: Speed, slow_motion_speed: NonZeroUsize) {
-        let time = Instant::now();
-
-        match slow_motion {
-            Speed::SlowMotion => {
-                let cur = self.wl.step_counter();
-                let max = cur + slow_motion_speed.get();
-                self.wl.wang_landau_while_acc(
-                    |ensemble, step, old_energy| ensemble.update_head_count(step, old_energy),
-                    |wl| wl.step_counter() < max && time.elapsed().as_micros() < 100,
-                );
-            }
-            Speed::Regular => {
-                self.wl.wang_landau_while_acc(
-                    |ensemble, step, old_energy| ensemble.update_head_count(step, old_energy),
-                    |_| time.elapsed().as_micros() < 5000,
-                );
+    /// Adds the current state to the history
+    fn record_convergence(&mut self) {
+        let (max_error, mean_error) = match self.true_density_log.as_ref() {
+            Some(exact) => {
+                let (estimate, _) = merge_windows(&self.windows, exact.len());
+                let errors: Vec<f64> = estimate
+                    .iter()
+                    .zip(exact.iter())
+                    .map(|(estimate, exact)| (estimate - exact).abs())
+                    .filter(|error| error.is_finite())
+                    .collect();
+                (
+                    errors.iter().copied().fold(0.0, f64::max),
+                    errors.iter().sum::<f64>() / errors.len() as f64,
+                )
             }
+            None => (f64::NAN, f64::NAN),
         };
+        self.history.push(ConvergencePoint {
+            steps: self.step_counter(),
+            log_f: self.log_f(),
+            max_error,
+            mean_error,
+            flatness: self
+                .windows
+                .iter()
+                .map(|window| window.flatness())
+                .fold(f64::INFINITY, f64::min),
+            stages: self
+                .windows
+                .iter()
+                .map(|window| window.stages())
+                .min()
+                .unwrap_or(0),
+        });
+    }
 
-        let wl_time = time.elapsed().as_micros();
-        let time = Instant::now();
-        let uniform = Uniform::new_inclusive(0.0, 1.0).expect("This is valid");
-        let num_coins = self.simple_sample_hist.bin_count() - 1;
+    /// Largest log f of all windows, i.e., the one of the window that is furthest from converging
+    pub fn log_f(&self) -> f64 {
+        self.windows
+            .iter()
+            .map(|window| window.log_f())
+            .fold(0.0, f64::max)
+    }
 
-        while time.elapsed().as_micros() < wl_time {
+    /// Steps of all windows combined
+    pub fn step_counter(&self) -> usize {
+        self.windows
+            .iter()
+            .map(|window| window.step_counter())
+            .sum()
+    }
+
+    pub fn set_log_f_threshold(&mut self, target_log_f: f64) {
+        self.parameters.target_log_f = target_log_f;
+        for window in self.windows.iter_mut() {
+            window.set_log_f_threshold(target_log_f);
+        }
+    }
+
+    /// Wang Landau for every window, each until its share of the time until `deadline` is used up
+    fn wang_landau_until(&mut self, deadline: Instant, max_steps: Option<NonZeroUsize>) -> u64 {
+        let count = self.windows.len();
+        let mut steps = 0;
+        for (i, window) in self.windows.iter_mut().enumerate() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let window_deadline = Instant::now() + remaining / (count - i) as u32;
+            let old = window.step_counter();
+            let max = max_steps.map_or(usize::MAX, |max| old + (max.get() / count).max(1));
+            window.wang_landau_while(|step_counter| {
+                step_counter < max && Instant::now() < window_deadline
+            });
+            steps += (window.step_counter() - old) as u64;
+        }
+        steps
+    }
+
+    /// Simple sampling until `deadline`, for comparison with Wang Landau
+    fn simple_sample_until(&mut self, deadline: Instant) {
+        while Instant::now() < deadline {
             for _ in 0..3 {
-                let mut num_heads = 0;
-                uniform
-                    .sample_iter(&mut self.rng)
-                    .take(num_coins)
-                    .filter(|&val| val <= 0.5)
-                    .for_each(|_| num_heads += 1);
-                self.simple_sample_hist.increment_quiet(num_heads);
+                for flip in self.simple_sample_flips.iter_mut() {
+                    *flip = self.heads_probability.throw(&mut self.rng);
+                }
+                let value = self.observable.measure(&self.simple_sample_flips);
+                self.simple_sample_hist.increment_quiet(value);
             }
         }
     }
@@ -319,21 +890,45 @@ impl Simulation {
         let total: usize = hist.iter().sum();
         let factor = (total as f64).recip();
         let mut estimate: Vec<_> = hist.iter().map(|&val| val as f64 * factor).collect();
-        if display == DisplayState::Log {
+        if display != DisplayState::Linear {
+            let coins = self.coins();
             estimate.iter_mut().for_each(|v| {
                 if *v == 0.0 {
                     *v = f64::NAN
                 } else {
-                    *v = v.log10()
+                    *v = display.convert_log10(v.log10(), coins)
                 }
             });
         }
         estimate
     }
+
+    /// Number of coins of every sequence
+    fn coins(&self) -> u32 {
+        self.simple_sample_flips.len() as u32
+    }
 }
 
-fn energy_fn(seq: &mut CoinFlipSequence<Pcg64>) -> Option<u32> {
-    Some(seq.head_count())
+/// Wang Landau gets the first half of the time slice, simple sampling the second half
+impl TimeSliced for Simulation {
+    fn step_budget(&mut self, deadline: Instant) -> u64 {
+        let start = Instant::now();
+        let halfway = start + deadline.saturating_duration_since(start) / 2;
+        let steps = self.wang_landau_until(halfway, None);
+        self.simple_sample_until(deadline);
+        self.record_convergence();
+        steps
+    }
+
+    fn step_budget_limited(&mut self, deadline: Instant, max_steps: NonZeroUsize) -> u64 {
+        let start = Instant::now();
+        let halfway = start + deadline.saturating_duration_since(start) / 2;
+        let steps = self.wang_landau_until(halfway, Some(max_steps));
+        // simple sampling gets as much time as Wang Landau took
+        self.simple_sample_until(Instant::now() + start.elapsed());
+        self.record_convergence();
+        steps
+    }
 }
 
 #[derive(Debug, Default, PartialEq, Clone, Copy)]
@@ -341,6 +936,8 @@ pub enum DisplayState {
     #[default]
     Log,
     Linear,
+    /// Large deviation rate function Φ = -(1/n) ln P over the observable divided by n
+    RateFunction,
 }
 
 impl DisplayState {
@@ -348,16 +945,39 @@ impl DisplayState {
         match self {
             Self::Linear => "Probability",
             Self::Log => "Log10 of Probability",
+            Self::RateFunction => "Rate function Φ",
+        }
+    }
+
+    /// Factor between the value of the observable and the x axis
+    fn x_scale(self, coins: u32) -> f64 {
+        match self {
+            Self::RateFunction => (coins as f64).recip(),
+            _ => 1.0,
+        }
+    }
+
+    /// Converts the log10 of a probability into what is displayed
+    fn convert_log10(self, log10_probability: f64, coins: u32) -> f64 {
+        match self {
+            Self::Linear => 10.0_f64.powf(log10_probability),
+            Self::Log => log10_probability,
+            Self::RateFunction => log10_to_rate(log10_probability, coins),
         }
     }
 }
 
-fn slice_to_line_or_points<'a>(slice: &[f64], name: &str, line_or_points: LineOrPoints) -> LoP<'a> {
+fn slice_to_line_or_points<'a>(
+    slice: &[f64],
+    x_scale: f64,
+    name: &str,
+    line_or_points: LineOrPoints,
+) -> LoP<'a> {
     let plot_points = PlotPoints::new(
         slice
             .iter()
             .enumerate()
-            .map(|(idx, val)| [idx as f64, *val])
+            .map(|(idx, val)| [idx as f64 * x_scale, *val])
             .collect(),
     );
 
@@ -398,6 +1018,29 @@ pub enum LineOrPoints {
     Points,
 }
 
+/// Returns true if the probability was changed
+pub fn heads_probability_slider(
+    ui: &mut egui::Ui,
+    heads_probability: &mut HeadsProbability,
+) -> bool {
+    let mut p = heads_probability.get();
+    ui.horizontal(|ui| {
+        ui.label("Heads probability");
+        ui.add(Slider::new(
+            &mut p,
+            HeadsProbability::MIN..=1.0 - HeadsProbability::MIN,
+        ))
+        .on_hover_text("Probability that a coin shows head. The further it is from 0.5, the more the rare tails move");
+    });
+    match HeadsProbability::new(p) {
+        Some(new) if new != *heads_probability => {
+            *heads_probability = new;
+            true
+        }
+        _ => false,
+    }
+}
+
 fn line_or_points_radio_btn(ui: &mut egui::Ui, current: &mut LineOrPoints, name: &str) {
     ui.horizontal(|ui| {
         ui.label(name);
@@ -0,0 +1,162 @@
+use super::super::coin_sequence_wl::CoinFlipSequence;
+use super::CoinObservable;
+use rand::Rng;
+use rand_pcg::Pcg64;
+use sampling::MarkovChain;
+use std::f64::consts::LOG10_E;
+
+/// The histogram counts as flat once its smallest entry is at least this fraction of the mean
+const FLATNESS: f64 = 0.8;
+
+/// # Wang Landau with the original refinement schedule
+///
+/// log f is halved every time the histogram is flat, all the way down to the threshold.
+/// The sampling crate always switches to the 1/t schedule, which is why this is implemented here
+#[derive(Debug)]
+pub struct ClassicWl {
+    ensemble: CoinFlipSequence<Pcg64>,
+    rng: Pcg64,
+    observable: CoinObservable,
+    /// Smallest value of the observable inside of the window
+    left: u32,
+    /// Current value of the observable
+    value: u32,
+    /// Natural logarithm of the (non normalized) density
+    log_density: Vec<f64>,
+    hist: Vec<usize>,
+    log_f: f64,
+    log_f_threshold: f64,
+    step_counter: usize,
+    check_flat_every: usize,
+    /// How often log f was halved
+    stages: u32,
+}
+
+impl ClassicWl {
+    /// Greedily moves `ensemble` into the window `left..=right`
+    pub fn new(
+        left: u32,
+        right: u32,
+        log_f_threshold: f64,
+        mut ensemble: CoinFlipSequence<Pcg64>,
+        rng: Pcg64,
+        check_flat_every: usize,
+        observable: CoinObservable,
+    ) -> Self {
+        let distance = |value: u32| {
+            if value < left {
+                left - value
+            } else {
+                value.saturating_sub(right)
+            }
+        };
+        let mut value = observable.measure(ensemble.flips());
+        while distance(value) > 0 {
+            let step = ensemble.m_step();
+            let mut new_value = value;
+            observable.update(&ensemble, &step, &mut new_value);
+            if distance(new_value) > distance(value) {
+                ensemble.undo_step_quiet(&step);
+            } else {
+                value = new_value;
+            }
+        }
+
+        let bins = (right - left + 1) as usize;
+        ClassicWl {
+            ensemble,
+            rng,
+            observable,
+            left,
+            value,
+            log_density: vec![0.0; bins],
+            hist: vec![0; bins],
+            log_f: 1.0,
+            log_f_threshold,
+            step_counter: 0,
+            check_flat_every,
+            stages: 0,
+        }
+    }
+
+    pub fn log_f(&self) -> f64 {
+        self.log_f
+    }
+
+    pub fn set_log_f_threshold(&mut self, log_f_threshold: f64) {
+        self.log_f_threshold = log_f_threshold;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.log_f <= self.log_f_threshold
+    }
+
+    pub fn step_counter(&self) -> usize {
+        self.step_counter
+    }
+
+    /// Number of refinement stages passed, i.e., how often log f was halved
+    pub fn stages(&self) -> u32 {
+        self.stages
+    }
+
+    /// Current value of the observable
+    pub fn energy(&self) -> u32 {
+        self.value
+    }
+
+    pub fn log_density_base10(&self) -> Vec<f64> {
+        self.log_density.iter().map(|val| val * LOG10_E).collect()
+    }
+
+    /// Histogram since the last refinement
+    pub fn hist(&self) -> &[usize] {
+        &self.hist
+    }
+
+    fn bin(&self, value: u32) -> Option<usize> {
+        value
+            .checked_sub(self.left)
+            .map(|bin| bin as usize)
+            .filter(|bin| *bin < self.log_density.len())
+    }
+
+    fn is_flat(&self) -> bool {
+        let mean = self.hist.iter().sum::<usize>() as f64 / self.hist.len() as f64;
+        let min = self.hist.iter().min().copied().unwrap_or(0);
+        min as f64 >= FLATNESS * mean
+    }
+
+    pub fn wang_landau_step(&mut self) {
+        self.step_counter += 1;
+        let step = self.ensemble.m_step();
+        let mut new_value = self.value;
+        self.observable
+            .update(&self.ensemble, &step, &mut new_value);
+
+        let old_bin = self.bin(self.value).unwrap();
+        let accept = match self.bin(new_value) {
+            Some(new_bin) => {
+                let prob = (self.log_density[old_bin] - self.log_density[new_bin]).exp();
+                self.rng.random::<f64>() < prob
+            }
+            // leaving the window is never allowed
+            None => false,
+        };
+        let bin = if accept {
+            self.value = new_value;
+            self.bin(new_value).unwrap()
+        } else {
+            self.ensemble.undo_step_quiet(&step);
+            old_bin
+        };
+        self.hist[bin] += 1;
+        self.log_density[bin] += self.log_f;
+
+        if self.step_counter % self.check_flat_every == 0 && self.is_flat() {
+            self.log_f *= 0.5;
+            self.stages += 1;
+            self.hist.iter_mut().for_each(|entry| *entry = 0);
+        }
+    }
+}
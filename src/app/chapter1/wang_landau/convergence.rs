@@ -0,0 +1,51 @@
+/// The history is thinned out once it has this many points
+const MAX_POINTS: usize = 2000;
+
+/// # State of a simulation after a time slice
+#[derive(Debug, Clone, Copy)]
+pub struct ConvergencePoint {
+    /// Steps of all windows combined
+    pub steps: usize,
+    /// Largest log f of all windows
+    pub log_f: f64,
+    /// Largest absolute difference between the log10 of the estimate and the analytic result
+    pub max_error: f64,
+    /// Mean absolute difference between the log10 of the estimate and the analytic result
+    pub mean_error: f64,
+    /// Smallest flatness (min/mean of the histogram) of all windows
+    pub flatness: f64,
+    /// Smallest number of refinement stages any window passed
+    pub stages: u32,
+}
+
+/// # How a simulation converged over time
+#[derive(Debug, Clone, Default)]
+pub struct ConvergenceHistory {
+    points: Vec<ConvergencePoint>,
+}
+
+impl ConvergenceHistory {
+    /// Points that do not contain new steps are ignored
+    pub fn push(&mut self, point: ConvergencePoint) {
+        if self
+            .points
+            .last()
+            .is_some_and(|last| last.steps == point.steps)
+        {
+            return;
+        }
+        if self.points.len() >= MAX_POINTS {
+            // every second point is dropped, the first one is kept
+            let mut index = 0;
+            self.points.retain(|_| {
+                index += 1;
+                index % 2 == 1
+            });
+        }
+        self.points.push(point);
+    }
+
+    pub fn points(&self) -> &[ConvergencePoint] {
+        &self.points
+    }
+}
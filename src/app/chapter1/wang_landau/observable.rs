@@ -0,0 +1,317 @@
+use super::super::coin_sequence_wl::{CoinFlip, CoinFlipMove, CoinFlipSequence, HeadsProbability};
+use serde::{Deserialize, Serialize};
+use statrs::distribution::{Binomial, Discrete};
+use std::{f64::consts::LOG10_E, num::NonZeroU32};
+
+/// Exact results without closed form are calculated in linear space,
+/// which works as long as every probability is larger than this
+const MIN_EXACT_PROBABILITY: f64 = 1e-300;
+/// Wang Landau only samples a longest run of 0 if it is at least this likely,
+/// see [CoinObservable::sampled_range]
+const MIN_SAMPLED_NO_RUN_PROBABILITY: f64 = 1e-5;
+/// Upper limit for the period of the weights of [CoinObservable::WeightedSum]
+pub const MAX_WEIGHT_PERIOD: u32 = 8;
+
+/// # Property of a coin flip sequence, whose probability distribution is estimated
+///
+/// Every value from 0 up to [CoinObservable::max_value] can be reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CoinObservable {
+    /// Number of heads
+    #[default]
+    HeadCount,
+    /// Length of the longest uninterrupted sequence of heads
+    LongestHeadRun,
+    /// Number of neighbouring coins that show different sides
+    Changes,
+    /// Every head counts with the weight `1 + (index % period)`,
+    /// i.e., the weights are 1, 2, ..., period, 1, 2, ...
+    WeightedSum { period: NonZeroU32 },
+}
+
+impl CoinObservable {
+    pub fn default_weighted_sum() -> Self {
+        Self::WeightedSum {
+            period: NonZeroU32::new(3).unwrap(),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::HeadCount => "Head count",
+            Self::LongestHeadRun => "Longest run of heads",
+            Self::Changes => "Head/tail changes",
+            Self::WeightedSum { .. } => "Weighted sum",
+        }
+    }
+
+    pub fn axis_label(self) -> &'static str {
+        match self {
+            Self::HeadCount => "Number of Heads",
+            Self::LongestHeadRun => "Longest run of Heads",
+            Self::Changes => "Number of Head/Tail changes",
+            Self::WeightedSum { .. } => "Weighted sum of Heads",
+        }
+    }
+
+    /// Label of the observable divided by the number of coins
+    pub fn rate_axis_label(self) -> &'static str {
+        match self {
+            Self::HeadCount => "Heads rate",
+            Self::LongestHeadRun => "Longest run of Heads / n",
+            Self::Changes => "Head/Tail changes / n",
+            Self::WeightedSum { .. } => "Weighted sum of Heads / n",
+        }
+    }
+
+    /// Weight of the coin at `index` if it shows head
+    fn weight(self, index: usize) -> u32 {
+        match self {
+            Self::WeightedSum { period } => 1 + (index % period.get() as usize) as u32,
+            _ => 1,
+        }
+    }
+
+    /// Largest possible value for a sequence of `coins` coins
+    pub fn max_value(self, coins: u32) -> u32 {
+        match self {
+            Self::HeadCount | Self::LongestHeadRun => coins,
+            Self::Changes => coins.saturating_sub(1),
+            Self::WeightedSum { .. } => (0..coins as usize).map(|i| self.weight(i)).sum(),
+        }
+    }
+
+    /// Values that Wang Landau samples.
+    ///
+    /// A longest run of 0 means that all coins show tail. Coming from a longest run of 1,
+    /// single flips would have to find this one sequence out of exponentially many,
+    /// so it is left out if it is unlikely. Its probability is negligible then anyway
+    pub fn sampled_range(self, coins: u32, heads_probability: HeadsProbability) -> (u32, u32) {
+        let no_run_probability = (1.0 - heads_probability.get()).powi(coins as i32);
+        let min = match self {
+            Self::LongestHeadRun if no_run_probability < MIN_SAMPLED_NO_RUN_PROBABILITY => 1,
+            _ => 0,
+        };
+        (min, self.max_value(coins))
+    }
+
+    /// Calculates the observable from scratch
+    pub fn measure(self, flips: &[CoinFlip]) -> u32 {
+        match self {
+            Self::HeadCount => flips.iter().filter(|&&flip| flip == CoinFlip::Head).count() as u32,
+            Self::LongestHeadRun => {
+                let mut longest = 0;
+                let mut current = 0;
+                for flip in flips {
+                    if *flip == CoinFlip::Head {
+                        current += 1;
+                        longest = longest.max(current);
+                    } else {
+                        current = 0;
+                    }
+                }
+                longest
+            }
+            Self::Changes => flips.windows(2).filter(|pair| pair[0] != pair[1]).count() as u32,
+            Self::WeightedSum { .. } => flips
+                .iter()
+                .enumerate()
+                .filter(|(_, &flip)| flip == CoinFlip::Head)
+                .map(|(index, _)| self.weight(index))
+                .sum(),
+        }
+    }
+
+    /// * Updates `value`, which has to be the value before `step`, to the value of `sequence`
+    /// * `sequence` has to be the state directly after `step`
+    pub fn update<R>(self, sequence: &CoinFlipSequence<R>, step: &CoinFlipMove, value: &mut u32) {
+        if sequence.is_unchanged_by(step) {
+            return;
+        }
+        let index = step.index();
+        match self {
+            Self::HeadCount => sequence.update_head_count(step, value),
+            Self::WeightedSum { .. } => match step.previous() {
+                CoinFlip::Head => *value -= self.weight(index),
+                CoinFlip::Tail => *value += self.weight(index),
+            },
+            Self::Changes => {
+                let flips = sequence.flips();
+                let neighbours = [index.checked_sub(1), Some(index + 1)];
+                for neighbour in neighbours.into_iter().flatten() {
+                    if let Some(&other) = flips.get(neighbour) {
+                        // the flipped coin now differs from its neighbour iff it did not before
+                        if other == flips[index] {
+                            *value -= 1;
+                        } else {
+                            *value += 1;
+                        }
+                    }
+                }
+            }
+            // the longest run can change anywhere, as runs merge or split
+            Self::LongestHeadRun => *value = self.measure(sequence.flips()),
+        }
+    }
+
+    /// There is a closed form for some observables. For the others the probabilities are
+    /// calculated directly, which is only done as long as they are larger than [MIN_EXACT_PROBABILITY]
+    pub fn has_exact_result(self, coins: NonZeroU32, heads_probability: HeadsProbability) -> bool {
+        match self {
+            Self::HeadCount => true,
+            Self::Changes if heads_probability.is_fair() => true,
+            _ => coins.get() <= max_exact_coins(heads_probability),
+        }
+    }
+
+    /// Log10 of the probability of every value.
+    /// None if there is no exact result, see [CoinObservable::has_exact_result]
+    pub fn exact_log10(
+        self,
+        coins: NonZeroU32,
+        heads_probability: HeadsProbability,
+    ) -> Option<Vec<f64>> {
+        if !self.has_exact_result(coins, heads_probability) {
+            return None;
+        }
+        let n = coins.get();
+        let p = heads_probability.get();
+        let probability = match self {
+            Self::HeadCount => return Some(binomial_log10(n, p)),
+            // for fair coins every pair of neighbours differs with probability 1/2,
+            // independent of the other pairs
+            Self::Changes if heads_probability.is_fair() => return Some(binomial_log10(n - 1, p)),
+            Self::Changes => {
+                // probability of the number of changes so far, if the last coin is head or tail
+                let mut head = vec![p];
+                let mut tail = vec![1.0 - p];
+                for _ in 1..n {
+                    let mut next_head = vec![0.0; head.len() + 1];
+                    let mut next_tail = vec![0.0; head.len() + 1];
+                    for changes in 0..head.len() {
+                        next_head[changes] += p * head[changes];
+                        next_head[changes + 1] += p * tail[changes];
+                        next_tail[changes] += (1.0 - p) * tail[changes];
+                        next_tail[changes + 1] += (1.0 - p) * head[changes];
+                    }
+                    head = next_head;
+                    tail = next_tail;
+                }
+                head.iter().zip(tail.iter()).map(|(h, t)| h + t).collect()
+            }
+            Self::LongestHeadRun => longest_run_probability(n as usize, p),
+            Self::WeightedSum { .. } => {
+                let mut probability = vec![1.0];
+                for index in 0..n as usize {
+                    let weight = self.weight(index) as usize;
+                    let mut next = vec![0.0; probability.len() + weight];
+                    for (sum, old) in probability.iter().enumerate() {
+                        next[sum] += (1.0 - p) * old;
+                        next[sum + weight] += p * old;
+                    }
+                    probability = next;
+                }
+                probability
+            }
+        };
+        Some(probability.into_iter().map(f64::log10).collect())
+    }
+}
+
+/// Largest number of coins for which the probabilities without closed form are calculated.
+/// The least likely sequence has the probability `min_side^coins`
+pub fn max_exact_coins(heads_probability: HeadsProbability) -> u32 {
+    (MIN_EXACT_PROBABILITY.log10() / heads_probability.min_side().log10()) as u32
+}
+
+fn binomial_log10(trials: u32, p: f64) -> Vec<f64> {
+    let binomial = Binomial::new(p, trials as u64).unwrap();
+    (0..=trials as u64)
+        .map(|k| LOG10_E * binomial.ln_pmf(k))
+        .collect()
+}
+
+/// Probability of every length of the longest run of heads in `n` flips,
+/// if every coin shows head with probability `p`.
+///
+/// The probabilities of the current run and the longest run so far are propagated flip by flip.
+/// Only positive numbers are added, so there is no cancellation, even for very likely heads.
+///
+/// `n` should not be larger than [max_exact_coins]
+fn longest_run_probability(n: usize, p: f64) -> Vec<f64> {
+    // state[longest][run] = P(longest run so far, current run of heads), with run <= longest
+    let mut state: Vec<Vec<f64>> = (0..=n).map(|longest| vec![0.0; longest + 1]).collect();
+    state[0][0] = 1.0;
+    for flips in 0..n {
+        // descending, such that the row above is already updated when the longest run grows
+        for longest in (0..=flips).rev() {
+            let (lower, upper) = state.split_at_mut(longest + 1);
+            let runs = &mut lower[longest];
+            upper[0][longest + 1] += p * runs[longest];
+            let total: f64 = runs.iter().sum();
+            runs.copy_within(0..longest, 1);
+            runs[1..].iter_mut().for_each(|prob| *prob *= p);
+            runs[0] = (1.0 - p) * total;
+        }
+    }
+    state.iter().map(|runs| runs.iter().sum()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Probability of every longest run of heads, by enumerating all sequences
+    fn brute_force_longest_run(n: usize, p: f64) -> Vec<f64> {
+        let mut probability = vec![0.0; n + 1];
+        let mut flips = vec![CoinFlip::Tail; n];
+        for bits in 0..1_u32 << n {
+            let mut prob = 1.0;
+            for (index, flip) in flips.iter_mut().enumerate() {
+                if bits & (1 << index) != 0 {
+                    *flip = CoinFlip::Head;
+                    prob *= p;
+                } else {
+                    *flip = CoinFlip::Tail;
+                    prob *= 1.0 - p;
+                }
+            }
+            probability[CoinObservable::LongestHeadRun.measure(&flips) as usize] += prob;
+        }
+        probability
+    }
+
+    #[test]
+    fn longest_run_matches_brute_force() {
+        for p in [0.1, 0.3, 0.5, 0.75, 0.9, 0.99] {
+            for n in [1, 2, 5, 12] {
+                let exact = longest_run_probability(n, p);
+                let brute_force = brute_force_longest_run(n, p);
+                assert_eq!(exact.len(), brute_force.len());
+                for (a, b) in exact.iter().zip(brute_force.iter()) {
+                    assert!((a - b).abs() <= 1e-12 * b, "p={p} n={n}: {a} vs {b}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn longest_run_without_cancellation() {
+        // without any head and with no two heads in a row
+        let p = 0.9;
+        let probability = longest_run_probability(60, p);
+        let no_head = (1.0 - p).powi(60);
+        assert!((probability[0] / no_head - 1.0).abs() < 1e-10);
+        // k isolated heads can be placed in binomial(61 - k, k) ways
+        let isolated: f64 = (1..=30)
+            .map(|k| {
+                let ways: f64 = (0..k)
+                    .map(|i| (61 - k - i) as f64 / (i + 1) as f64)
+                    .product();
+                ways * p.powi(k) * (1.0 - p).powi(60 - k)
+            })
+            .sum();
+        assert!((probability[1] / isolated - 1.0).abs() < 1e-10);
+        assert!(probability.iter().all(|prob| *prob > 0.0));
+    }
+}
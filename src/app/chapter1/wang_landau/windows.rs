@@ -0,0 +1,275 @@
+use super::super::coin_sequence_wl::CoinFlipSequence;
+use super::{ClassicWl, CoinObservable, ThisWl};
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+use sampling::{
+    HistU32Fast, Histogram, WangLandau, WangLandau1T, WangLandauEnergy, WangLandauHist,
+    WangLandauMode,
+};
+use serde::{Deserialize, Serialize};
+
+/// How log f is decreased during the simulation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Refinement {
+    /// Halving log f until it falls below 1/t, then log f = 1/t (Belardinelli and Pereyra)
+    #[default]
+    OneOverT,
+    /// Halving log f whenever the histogram is flat
+    Classic,
+}
+
+impl Refinement {
+    pub fn name(self) -> &'static str {
+        match self {
+            Refinement::OneOverT => "1/t",
+            Refinement::Classic => "classic",
+        }
+    }
+}
+
+#[derive(Debug)]
+enum WindowWl {
+    OneOverT(ThisWl),
+    Classic(ClassicWl),
+}
+
+/// # One energy window of a Wang Landau simulation
+///
+/// Only sequences with a value of the observable inside of the window are visited
+#[derive(Debug)]
+pub struct Window {
+    /// Smallest value of the observable inside of the window
+    pub left: u32,
+    wl: WindowWl,
+    observable: CoinObservable,
+    /// Last log f of the 1/t simulation before it switched to log f = 1/t
+    last_halved_log_f: f64,
+}
+
+impl Window {
+    /// `ensemble` is moved into the window before the simulation starts
+    pub fn new(
+        left: u32,
+        right: u32,
+        ensemble: CoinFlipSequence<Pcg64>,
+        target_log_f: f64,
+        refinement: Refinement,
+        observable: CoinObservable,
+        rng: &mut Pcg64,
+    ) -> Self {
+        let wl_rng = Pcg64::from_rng(rng);
+
+        let check_refine_every = (right - left).max(1) as usize * 10;
+
+        let wl = match refinement {
+            Refinement::OneOverT => {
+                let histogram = HistU32Fast::new_inclusive(left, right).unwrap();
+
+                let mut wl = WangLandau1T::new(
+                    target_log_f,
+                    ensemble,
+                    wl_rng,
+                    1,
+                    histogram,
+                    check_refine_every,
+                )
+                .unwrap();
+
+                // Wl needs to be initialized, which also moves the sequence into the window
+                wl.init_greedy_heuristic(|seq| Some(observable.measure(seq.flips())), None)
+                    .unwrap();
+                WindowWl::OneOverT(wl)
+            }
+            Refinement::Classic => WindowWl::Classic(ClassicWl::new(
+                left,
+                right,
+                target_log_f,
+                ensemble,
+                wl_rng,
+                check_refine_every,
+                observable,
+            )),
+        };
+
+        Window {
+            left,
+            wl,
+            observable,
+            last_halved_log_f: 1.0,
+        }
+    }
+
+    pub fn log_f(&self) -> f64 {
+        match &self.wl {
+            WindowWl::OneOverT(wl) => wl.log_f(),
+            WindowWl::Classic(wl) => wl.log_f(),
+        }
+    }
+
+    pub fn set_log_f_threshold(&mut self, target_log_f: f64) {
+        match &mut self.wl {
+            WindowWl::OneOverT(wl) => {
+                wl.set_log_f_threshold(target_log_f).unwrap();
+            }
+            WindowWl::Classic(wl) => wl.set_log_f_threshold(target_log_f),
+        }
+    }
+
+    pub fn step_counter(&self) -> usize {
+        match &self.wl {
+            WindowWl::OneOverT(wl) => wl.step_counter(),
+            WindowWl::Classic(wl) => wl.step_counter(),
+        }
+    }
+
+    /// Number of refinement stages passed.
+    /// For 1/t these are the halvings of log f, the 1/t phase counts as one more stage
+    pub fn stages(&self) -> u32 {
+        match &self.wl {
+            WindowWl::OneOverT(wl) => {
+                let halvings = (-self.last_halved_log_f.log2()).round() as u32;
+                match wl.mode() {
+                    WangLandauMode::RefineOriginal => halvings,
+                    WangLandauMode::Refine1T => halvings + 1,
+                }
+            }
+            WindowWl::Classic(wl) => wl.stages(),
+        }
+    }
+
+    /// Smallest entry of the histogram of the current stage divided by its mean.
+    /// The 1/t simulation does not reset its histogram once log f = 1/t
+    pub fn flatness(&self) -> f64 {
+        let hist = match &self.wl {
+            WindowWl::OneOverT(wl) => wl.hist().hist().as_slice(),
+            WindowWl::Classic(wl) => wl.hist(),
+        };
+        let sum: usize = hist.iter().sum();
+        if sum == 0 {
+            return 0.0;
+        }
+        let mean = sum as f64 / hist.len() as f64;
+        hist.iter().min().copied().unwrap_or(0) as f64 / mean
+    }
+
+    /// Current value of the observable
+    pub fn energy(&self) -> Option<u32> {
+        match &self.wl {
+            WindowWl::OneOverT(wl) => wl.energy().copied(),
+            WindowWl::Classic(wl) => Some(wl.energy()),
+        }
+    }
+
+    /// Non normalized estimate of the log10 of the probability of the bins in the window
+    pub fn log_density_base10(&self) -> Vec<f64> {
+        match &self.wl {
+            WindowWl::OneOverT(wl) => wl.log_density_base10(),
+            WindowWl::Classic(wl) => wl.log_density_base10(),
+        }
+    }
+
+    /// Performs Wang Landau steps until the window is finished
+    /// or `condition` is false for the current step counter
+    pub fn wang_landau_while<C>(&mut self, mut condition: C)
+    where
+        C: FnMut(usize) -> bool,
+    {
+        match &mut self.wl {
+            WindowWl::OneOverT(wl) => {
+                let last_halved_log_f = &mut self.last_halved_log_f;
+                let observable = self.observable;
+                wl.wang_landau_while_acc(
+                    |ensemble, step, old_energy| observable.update(ensemble, step, old_energy),
+                    |wl| {
+                        // log f is a power of two until the switch to 1/t
+                        if wl.mode() == WangLandauMode::RefineOriginal {
+                            *last_halved_log_f = wl.log_f();
+                        }
+                        condition(wl.step_counter())
+                    },
+                )
+            }
+            WindowWl::Classic(wl) => {
+                while !wl.is_finished() && condition(wl.step_counter()) {
+                    wl.wang_landau_step();
+                }
+            }
+        }
+    }
+}
+
+/// Splits `min..=max` into `windows` windows of roughly equal size.
+/// Every window also contains the last `overlap` bins of the previous window.
+///
+/// Returns the inclusive ranges of the windows
+pub fn window_ranges(min: u32, max: u32, windows: u32, overlap: u32) -> Vec<(u32, u32)> {
+    let bins = max - min + 1;
+    // every window should have at least two bins of its own
+    let windows = windows.clamp(1, (bins / 2).max(1));
+    // without overlap the windows cannot be merged
+    let overlap = overlap.max(1);
+    (0..windows)
+        .map(|i| {
+            let start = min + i * bins / windows;
+            let end = min + (i + 1) * bins / windows - 1;
+            if i == 0 {
+                (start, end)
+            } else {
+                (start.saturating_sub(overlap).max(min), end)
+            }
+        })
+        .collect()
+}
+
+/// Shifts the log10 estimates of the windows such that they agree on average
+/// in the overlap with the previous window. In the overlaps the merged estimate
+/// is the mean of the windows.
+///
+/// Returns the merged estimate, normalized such that it sums to 1,
+/// and the shifted estimates of the windows, which fit to the merged estimate
+pub fn merge_windows(windows: &[Window], bins: usize) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let mut aligned: Vec<Vec<f64>> = Vec::with_capacity(windows.len());
+    for (i, window) in windows.iter().enumerate() {
+        let mut estimate = window.log_density_base10();
+        if let Some(previous) = i.checked_sub(1).map(|j| (&windows[j], &aligned[j])) {
+            let (previous_window, previous_estimate) = previous;
+            let previous_left = previous_window.left as usize;
+            let left = window.left as usize;
+            let overlap =
+                left..(previous_left + previous_estimate.len()).min(left + estimate.len());
+            let count = overlap.len().max(1) as f64;
+            let shift = overlap
+                .map(|k| previous_estimate[k - previous_left] - estimate[k - left])
+                .sum::<f64>()
+                / count;
+            estimate.iter_mut().for_each(|val| *val += shift);
+        }
+        aligned.push(estimate);
+    }
+
+    let mut sum = vec![0.0; bins];
+    let mut count = vec![0_u32; bins];
+    for (window, estimate) in windows.iter().zip(aligned.iter()) {
+        for (k, val) in estimate.iter().enumerate() {
+            sum[window.left as usize + k] += val;
+            count[window.left as usize + k] += 1;
+        }
+    }
+    let mut merged: Vec<f64> = sum
+        .iter()
+        .zip(count.iter())
+        .map(|(sum, count)| sum / *count as f64)
+        .collect();
+
+    // bins outside of all windows are NaN
+    let reference = windows.first().map_or(0, |window| window.left as usize);
+    let before = merged[reference];
+    sampling::norm_log10_sum_to_1(&mut merged);
+    let shift = merged[reference] - before;
+    aligned
+        .iter_mut()
+        .flat_map(|estimate| estimate.iter_mut())
+        .for_each(|val| *val += shift);
+
+    (merged, aligned)
+}
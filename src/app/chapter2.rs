@@ -0,0 +1,17 @@
+use crate::dark_magic::BoxedAnything;
+
+use super::Chapter2;
+
+mod biased_sampling;
+
+pub fn chapter_2_switch(which: &Chapter2, any: &mut BoxedAnything, ctx: &egui::Context) {
+    match which {
+        Chapter2::First => {
+            let some: &mut u32 = any.to_something_or_default_mut();
+            *some = 10;
+        }
+        Chapter2::BiasedSampling => {
+            biased_sampling::biased_sampling_gui(any, ctx);
+        }
+    }
+}
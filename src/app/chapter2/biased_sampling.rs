@@ -0,0 +1,401 @@
+use crate::app::chapter1::coin_sequence_wl::HeadsProbability;
+use crate::app::chapter1::parallel_tempering::{
+    get_color, CoinSequence, EnergyModel, SidePanelView, Temperature,
+};
+use crate::app::chapter1::wang_landau::{calc_true_log, heads_probability_slider};
+use crate::dark_magic::{BoxedAnything, Scheduler, TimeSliced};
+use crate::misc::*;
+use derivative::Derivative;
+use egui::{Button, CentralPanel, DragValue, Slider};
+use egui_plot::{Legend, Line, MarkerShape, Plot, Points, VLine};
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+use sampling::Histogram;
+use std::{f64::consts::LOG10_E, num::NonZeroU32};
+use web_time::Instant;
+
+const MAX_COINS: u32 = 10000;
+/// The tilt slider goes from `-MAX_TILT` to `MAX_TILT`
+const MAX_TILT: f64 = 5.0;
+/// A bin counts as reliably covered once it contains this many effectively independent samples,
+/// so the relative error of its probability is about `1 / sqrt(MIN_EFFECTIVE_SAMPLES)`
+const MIN_EFFECTIVE_SAMPLES: f64 = 100.0;
+/// After a change of the tilt this many relaxation times are discarded as burn-in
+const BURN_IN_RELAXATIONS: f64 = 10.0;
+
+#[derive(Debug, Derivative)]
+#[derivative(Default)]
+pub struct BiasedSamplingData {
+    #[derivative(Default(value = "NonZeroU32::new(100).unwrap()"))]
+    coins: NonZeroU32,
+    heads_probability: HeadsProbability,
+    /// Every head multiplies the weight of a sequence by `exp(tilt)`
+    #[derivative(Default(value = "1.0"))]
+    tilt: f64,
+    seed: u64,
+    sampler: Option<BiasedSampler>,
+    side_panel: SidePanelView,
+    scheduler: Scheduler,
+}
+
+impl BiasedSamplingData {
+    fn start_sampling(&mut self) {
+        self.sampler = Some(BiasedSampler::new(
+            self.coins,
+            self.heads_probability,
+            self.tilt,
+            Pcg64::seed_from_u64(self.seed),
+        ));
+    }
+}
+
+/// # Metropolis sampling of coin sequences at a single temperature
+///
+/// A sequence with `k` heads has the weight `exp(tilt * k)` on top of its probability,
+/// which is the Boltzmann weight `exp(-E/T)` of parallel tempering
+/// with `E = k / n` and `T = -1 / (tilt * n)`
+#[derive(Debug)]
+pub struct BiasedSampler {
+    temperature: Temperature<CoinSequence>,
+    rng: Pcg64,
+    heads_probability: HeadsProbability,
+    tilt: f64,
+    /// Sweeps that still have to be discarded before anything is recorded
+    burn_in_sweeps: u64,
+}
+
+impl BiasedSampler {
+    pub fn new(
+        coins: NonZeroU32,
+        heads_probability: HeadsProbability,
+        tilt: f64,
+        mut rng: Pcg64,
+    ) -> Self {
+        let config = CoinSequence::random(&heads_probability, coins, &mut rng);
+        let temperature = Temperature::new(
+            tilt_to_temperature(tilt, coins),
+            config,
+            MarkerShape::Circle,
+            0,
+            0,
+        );
+        let mut sampler = Self {
+            temperature,
+            rng,
+            heads_probability,
+            tilt,
+            burn_in_sweeps: 0,
+        };
+        sampler.start_burn_in();
+        sampler
+    }
+
+    fn coins(&self) -> u32 {
+        self.temperature.config.max_level() as u32
+    }
+
+    /// Continues with the current sequence, which first has to relax to the new tilt.
+    /// The statistics start from scratch after the burn-in
+    pub fn set_tilt(&mut self, tilt: f64) {
+        self.tilt = tilt;
+        self.temperature.temperature =
+            tilt_to_temperature(tilt, NonZeroU32::new(self.coins()).unwrap());
+        self.start_burn_in();
+    }
+
+    /// Every coin is an independent two state Markov chain under the tilted weight.
+    /// A proposal of the coin changes it with probability `p min(1, exp(tilt))` or
+    /// `(1 - p) min(1, exp(-tilt))`, and every coin is proposed about once per sweep,
+    /// so the distance to equilibrium shrinks by `exp(-rate)` per sweep with the sum as rate
+    fn relaxation_rate(&self) -> f64 {
+        let p = self.heads_probability.get();
+        p * self.tilt.exp().min(1.0) + (1.0 - p) * (-self.tilt).exp().min(1.0)
+    }
+
+    fn start_burn_in(&mut self) {
+        self.burn_in_sweeps = (BURN_IN_RELAXATIONS / self.relaxation_rate()).ceil() as u64;
+        self.reset_statistics();
+    }
+
+    fn reset_statistics(&mut self) {
+        self.temperature.hist.reset();
+        self.temperature.acceptance.reset();
+        self.temperature.autocorrelation.reset();
+    }
+
+    /// Sweeps that are still discarded before anything is recorded
+    pub fn burn_in_sweeps(&self) -> u64 {
+        self.burn_in_sweeps
+    }
+
+    fn hits(&self) -> &[usize] {
+        self.temperature.hist.hist().as_slice()
+    }
+
+    /// Number of samples in the histogram
+    pub fn samples(&self) -> usize {
+        self.hits().iter().sum()
+    }
+
+    /// Probability of a head under the tilted weight, i.e., where the histogram peaks
+    pub fn tilted_heads_probability(&self) -> f64 {
+        let p = self.heads_probability.get();
+        let tilted = p * self.tilt.exp();
+        tilted / (1.0 - p + tilted)
+    }
+
+    /// Normalized histogram of the tilted distribution
+    pub fn tilted_distribution(&self) -> Vec<f64> {
+        let factor = (self.samples() as f64).recip();
+        self.hits()
+            .iter()
+            .map(|&hits| hits as f64 * factor)
+            .collect()
+    }
+
+    /// Log10 of the unbiased probability, NaN where the histogram is empty.
+    ///
+    /// The weight is undone via `P(k) = P_tilt(k) exp(-tilt * k) Z`.
+    /// For coins the normalization `Z = (1 - p + p exp(tilt))^n` is known exactly
+    pub fn reweighted_log10(&self) -> Vec<f64> {
+        let p = self.heads_probability.get();
+        let ln_z = self.coins() as f64 * (1.0 - p + p * self.tilt.exp()).ln();
+        self.tilted_distribution()
+            .iter()
+            .enumerate()
+            .map(|(heads, &prob)| {
+                if prob == 0.0 {
+                    f64::NAN
+                } else {
+                    (prob.ln() - self.tilt * heads as f64 + ln_z) * LOG10_E
+                }
+            })
+            .collect()
+    }
+
+    /// Integrated autocorrelation time of the number of heads in sweeps,
+    /// None as long as it cannot be estimated reliably
+    pub fn tau_int(&self) -> Option<f64> {
+        self.temperature
+            .autocorrelation
+            .integrated_time()
+            .filter(|time| time.reliable)
+            .map(|time| time.tau_int)
+    }
+
+    /// Smallest and largest number of heads with at least [MIN_EFFECTIVE_SAMPLES]
+    /// effectively independent samples.
+    ///
+    /// The hits of a bin are counted every step, i.e., `coins` times per sweep,
+    /// and only every `2 tau_int` sweeps give an independent sample
+    pub fn reliable_range(&self) -> Option<(usize, usize)> {
+        let tau_int = self.tau_int()?;
+        let min_hits = MIN_EFFECTIVE_SAMPLES * 2.0 * tau_int * self.coins() as f64;
+        let hits = self.hits();
+        let first = hits.iter().position(|&hits| hits as f64 >= min_hits)?;
+        let last = hits.iter().rposition(|&hits| hits as f64 >= min_hits)?;
+        Some((first, last))
+    }
+}
+
+/// One sweep, i.e., one step per coin, at a time
+impl TimeSliced for BiasedSampler {
+    fn step_budget(&mut self, deadline: Instant) -> u64 {
+        let coins = self.coins() as u64;
+        let mut steps = 0;
+        while Instant::now() < deadline {
+            for _ in 0..coins {
+                self.temperature.markov_step(&mut self.rng);
+            }
+            steps += coins;
+            if self.burn_in_sweeps > 0 {
+                self.burn_in_sweeps -= 1;
+                if self.burn_in_sweeps == 0 {
+                    self.reset_statistics();
+                }
+            } else {
+                let level = self.temperature.energy_level() as f64;
+                self.temperature.autocorrelation.push(level);
+            }
+        }
+        steps
+    }
+}
+
+/// Temperature of parallel tempering that corresponds to `tilt`
+fn tilt_to_temperature(tilt: f64, coins: NonZeroU32) -> f64 {
+    -(tilt * coins.get() as f64).recip()
+}
+
+pub fn biased_sampling_gui(any: &mut BoxedAnything, ctx: &egui::Context) {
+    let data: &mut BiasedSamplingData = any.to_something_or_default_mut();
+    let is_dark_mode = ctx.style().visuals.dark_mode;
+
+    if data.sampler.is_none() {
+        data.start_sampling();
+    }
+
+    match data.side_panel {
+        SidePanelView::Default => {
+            let screen_width = ctx.content_rect().width();
+            let is_desktop = screen_width > 600.0;
+            data.side_panel = if is_desktop {
+                SidePanelView::Shown
+            } else {
+                SidePanelView::Hidden
+            };
+        }
+        SidePanelView::Shown => {
+            egui::SidePanel::left("BiasedLeft").show(ctx, |ui| {
+                if ui.add(Button::new("Hide side panel")).clicked() {
+                    data.side_panel = SidePanelView::Hidden;
+                }
+
+                ui.horizontal(|ui| {
+                    let old = data.coins;
+                    ui.label("Number of coins");
+                    ui.add(DragValue::new(&mut data.coins).range(1..=MAX_COINS));
+                    if old != data.coins {
+                        data.start_sampling();
+                    }
+                });
+
+                if heads_probability_slider(ui, &mut data.heads_probability) {
+                    data.start_sampling();
+                }
+
+                ui.horizontal(|ui| {
+                    let old = data.tilt;
+                    ui.label("Tilt");
+                    ui.add(Slider::new(&mut data.tilt, -MAX_TILT..=MAX_TILT))
+                        .on_hover_text("Every head multiplies the weight of a sequence by exp(tilt). Positive values favour heads, negative values tails");
+                    if old != data.tilt {
+                        if let Some(sampler) = data.sampler.as_mut() {
+                            sampler.set_tilt(data.tilt);
+                        }
+                    }
+                });
+                ui.label(format!(
+                    "Temperature: T = {:.4}",
+                    tilt_to_temperature(data.tilt, data.coins)
+                ))
+                .on_hover_text("Temperature of the Boltzmann weight exp(-E/T) with E = heads rate, as used by parallel tempering");
+
+                ui.horizontal(|ui| {
+                    ui.label("Rng Seed:");
+                    ui.add(DragValue::new(&mut data.seed).speed(1));
+                    if ui.add(Button::new("Restart")).clicked() {
+                        data.start_sampling();
+                    }
+                });
+
+                if let Some(sampler) = data.sampler.as_ref() {
+                    ui.separator();
+                    if sampler.burn_in_sweeps() > 0 {
+                        ui.label(format!("Burn-in: {} sweeps left", sampler.burn_in_sweeps()))
+                            .on_hover_text("The sequence first relaxes to the current tilt, nothing is recorded in the meantime");
+                    }
+                    ui.label(format!("Samples: {:e}", sampler.samples()));
+                    ui.label(format!(
+                        "Acceptance rate: {:.3}",
+                        sampler.temperature.acceptance.acceptance_rate()
+                    ));
+                    ui.label(format!(
+                        "Tilted heads probability: {:.4}",
+                        sampler.tilted_heads_probability()
+                    ))
+                    .on_hover_text("The histogram peaks at this heads rate");
+                    match sampler.tau_int() {
+                        Some(tau_int) => ui.label(format!("τ_int: {tau_int:.2} sweeps")),
+                        None => ui.label("τ_int: not known yet"),
+                    }
+                    .on_hover_text("Integrated autocorrelation time of the number of heads");
+                    let coins = sampler.coins() as f64;
+                    match sampler.reliable_range() {
+                        Some((first, last)) => ui.label(format!(
+                            "Reliably covered: {:.4} ≤ x ≤ {:.4}",
+                            first as f64 / coins,
+                            last as f64 / coins
+                        )),
+                        None => ui.label("Nothing is reliably covered yet"),
+                    }
+                    .on_hover_text(format!(
+                        "Heads rates x with at least {MIN_EFFECTIVE_SAMPLES} effectively independent samples, according to τ_int"
+                    ));
+                }
+            });
+        }
+        SidePanelView::Hidden => {
+            egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+                if ui.button("show side panel").clicked() {
+                    data.side_panel = SidePanelView::Shown;
+                }
+            });
+        }
+    }
+
+    let Some(sampler) = data.sampler.as_mut() else {
+        return;
+    };
+
+    CentralPanel::default().show(ctx, |ui| {
+        let coins = sampler.coins();
+        let x_factor = (coins as f64).recip();
+        let to_points = |slice: &[f64]| -> Vec<[f64; 2]> {
+            slice
+                .iter()
+                .enumerate()
+                .map(|(heads, val)| [heads as f64 * x_factor, *val])
+                .collect()
+        };
+
+        let x_label = get_rich_text_size("Heads rate x", 15.0);
+        let half_height = ui.available_height() * 0.5;
+
+        let tilted = to_points(&sampler.tilted_distribution());
+        Plot::new("Biased_hist_HASH")
+            .height(half_height)
+            .x_axis_label(x_label.clone())
+            .y_axis_label(get_rich_text_size("Tilted probability", 15.0))
+            .legend(Legend::default())
+            .show(ui, |plot_ui| {
+                plot_ui.line(
+                    Line::new("Tilted histogram", tilted)
+                        .color(get_color(1, is_dark_mode))
+                        .fill(0.0),
+                );
+            });
+
+        let analytic = calc_true_log(NonZeroU32::new(coins).unwrap(), sampler.heads_probability);
+        let reweighted = to_points(&sampler.reweighted_log10());
+        let reliable_range = sampler.reliable_range();
+        Plot::new("Biased_reweighted_HASH")
+            .x_axis_label(x_label)
+            .y_axis_label(get_rich_text_size("Log10 of Probability", 15.0))
+            .legend(Legend::default())
+            .show(ui, |plot_ui| {
+                plot_ui.line(
+                    Line::new("Analytic", to_points(&analytic))
+                        .color(get_color(2, is_dark_mode))
+                        .width(3.0),
+                );
+                plot_ui.points(
+                    Points::new("Reweighted", reweighted)
+                        .radius(4.0)
+                        .color(get_color(1, is_dark_mode)),
+                );
+                if let Some((first, last)) = reliable_range {
+                    for heads in [first, last] {
+                        plot_ui.vline(
+                            VLine::new("Reliable range", heads as f64 * x_factor)
+                                .color(get_color(5, is_dark_mode))
+                                .style(egui_plot::LineStyle::dashed_loose()),
+                        );
+                    }
+                }
+            });
+    });
+
+    data.scheduler.run(ctx, None, &mut [sampler]);
+    ctx.request_repaint();
+}
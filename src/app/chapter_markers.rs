@@ -1,9 +1,10 @@
+use serde::{Deserialize, Serialize};
 use std::marker;
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 use strum_macros::EnumString;
 
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, EnumIter, Serialize, Deserialize)]
 pub enum ChapterAnchor {
     Chapter1(Chapter1),
     Chapter2(Chapter2),
@@ -22,7 +23,9 @@ impl ChapterAnchor {
     }
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, EnumIter, EnumString)]
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Hash, EnumIter, EnumString, Serialize, Deserialize,
+)]
 pub enum Chapter1 {
     #[default]
     #[strum(ascii_case_insensitive)]
@@ -35,11 +38,15 @@ pub enum Chapter1 {
     WangLandau,
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, EnumIter, EnumString)]
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Hash, EnumIter, EnumString, Serialize, Deserialize,
+)]
 pub enum Chapter2 {
     #[default]
     #[strum(ascii_case_insensitive)]
     First,
+    #[strum(ascii_case_insensitive)]
+    BiasedSampling,
 }
 
 pub trait ChapterReading {
@@ -63,8 +70,26 @@ where
     }
 }
 
+/// Splits a link like `#Chapter1-WangLandau?coins=200&seed=7` into
+/// the anchor and the key value pairs of the query
+pub fn split_link(link: &str) -> (&str, Vec<(&str, &str)>) {
+    let link = link.strip_prefix('#').unwrap_or(link);
+    match link.split_once('?') {
+        None => (link, Vec::new()),
+        Some((anchor, query)) => {
+            let pairs = query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .collect();
+            (anchor, pairs)
+        }
+    }
+}
+
 impl ChapterAnchor {
+    /// The query of the link, if any, is ignored
     pub fn read_str(url_infos: &str) -> Option<Self> {
+        let (url_infos, _) = split_link(url_infos);
         let mut iter = url_infos.split('-');
         let (chapter_str, jump_str) = match (iter.next(), iter.next()) {
             (Some(ch_s), Some(jp_s)) => (ch_s, jp_s),
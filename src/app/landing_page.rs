@@ -1,13 +1,26 @@
-use egui::FontDefinitions;
+use egui::{FontDefinitions, ThemePreference};
+use strum::IntoEnumIterator;
 
-use crate::{dark_magic::BoxedAnything, misc};
+use crate::{dark_magic::KeyedAnything, misc};
 
-use super::{chapter1, ChapterAnchor, GlobalContextMenu, MenuOptions};
+use super::{
+    chapter1, chapter2, split_link, Chapter1, ChapterAnchor, GlobalContextMenu, MenuOptions,
+};
+
+/// Did the user opt in to remembering the settings?
+const REMEMBER_KEY: &str = "remember";
+const MENU_KEY: &str = "menu";
+const THEME_KEY: &str = "theme";
 
 pub struct AppState {
     pub menu_options: MenuOptions,
     pub text: String,
-    pub anything: BoxedAnything,
+    /// Every page keeps its own state, even when another page is visited
+    pub pages: KeyedAnything<ChapterAnchor>,
+    /// Copy of the theme chosen in the menu, as saving has no access to the egui context
+    theme: ThemePreference,
+    /// Last link that was followed, such that its parameters are only applied once
+    followed_link: String,
 }
 
 impl AppState {
@@ -16,49 +29,146 @@ impl AppState {
         // This is also where you can customize the look and feel of egui using
         // `cc.egui_ctx.set_visuals` and `cc.egui_ctx.set_fonts`.
 
-        // here I do NOT save the app state
-
         eframe.egui_ctx.set_fonts(FontDefinitions::default());
-        AppState {
+        let mut state = AppState {
             menu_options: MenuOptions::default(),
             text: String::new(),
-            anything: BoxedAnything::new(()),
+            pages: KeyedAnything::default(),
+            theme: ThemePreference::default(),
+            followed_link: String::new(),
+        };
+        if let Some(storage) = eframe.storage {
+            if eframe::get_value(storage, REMEMBER_KEY) == Some(true) {
+                state.restore(storage, &eframe.egui_ctx);
+            }
+        }
+        state
+    }
+
+    /// Navigates to a link like `#Chapter1-WangLandau?coins=200&seed=7`,
+    /// which also sets up the demo with the parameters of the link.
+    ///
+    /// Following the same link again does nothing
+    pub fn follow_link(&mut self, link: &str) {
+        if link == self.followed_link {
+            return;
+        }
+        self.followed_link = link.to_owned();
+        let (anchor, query) = split_link(link);
+        if anchor.is_empty() {
+            return;
+        }
+        self.menu_options.anchor = ChapterAnchor::read_str(anchor).unwrap_or(ChapterAnchor::Index);
+        if let ChapterAnchor::Chapter1(which) = self.menu_options.anchor {
+            if !query.is_empty() {
+                let anything = self.pages.get_mut(self.menu_options.anchor);
+                chapter1::follow_link_query(&which, anything, &query);
+            }
+        }
+    }
+
+    /// Link to the current page, including the parameters of the demo
+    pub fn link(&self) -> String {
+        let anchor = self.menu_options.anchor;
+        let query = match anchor {
+            ChapterAnchor::Chapter1(which) => self
+                .pages
+                .get(&anchor)
+                .and_then(|any| chapter1::link_query(&which, any)),
+            _ => None,
+        };
+        match query {
+            Some(query) => format!("{}?{query}", anchor.get_string()),
+            None => anchor.get_string(),
+        }
+    }
+
+    fn restore(&mut self, storage: &dyn eframe::Storage, ctx: &egui::Context) {
+        if let Some(menu_options) = eframe::get_value(storage, MENU_KEY) {
+            self.menu_options = menu_options;
+        }
+        self.menu_options.remember = true;
+        if let Some(theme) = eframe::get_value::<ThemePreference>(storage, THEME_KEY) {
+            ctx.set_theme(theme);
+            self.theme = theme;
+        }
+        for which in Chapter1::iter() {
+            let anchor = ChapterAnchor::Chapter1(which);
+            chapter1::load_parameters(
+                &which,
+                self.pages.get_mut(anchor),
+                storage,
+                &anchor.get_string(),
+            );
         }
     }
 }
 
 impl eframe::App for AppState {
-    /// Called by the frame work to save state before shutdown.
-    fn save(&mut self, _: &mut dyn eframe::Storage) {
-        // I don't save the app state!
+    /// Called by the frame work to save state before shutdown and every now and then.
+    ///
+    /// Nothing but the opt out is stored, unless the user opted in
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, REMEMBER_KEY, &self.menu_options.remember);
+        if !self.menu_options.remember {
+            return;
+        }
+        eframe::set_value(storage, MENU_KEY, &self.menu_options);
+        eframe::set_value(storage, THEME_KEY, &self.theme);
+        for which in Chapter1::iter() {
+            let anchor = ChapterAnchor::Chapter1(which);
+            if let Some(any) = self.pages.get(&anchor) {
+                chapter1::save_parameters(&which, any, storage, &anchor.get_string());
+            }
+        }
+    }
+
+    /// The egui memory is not stored, as it is not covered by the opt in
+    fn persist_egui_memory(&self) -> bool {
+        false
     }
 
     /// Called each time the UI needs repainting, which may be many times per second.
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Put your widgets into a `SidePanel`, `TopBottomPanel`, `CentralPanel`, `Window` or `Area`.
         // For inspiration and more examples, go to https://emilk.github.io/egui
-        let old_anchor = self.menu_options.anchor;
         #[cfg(target_arch = "wasm32")]
-        if let Some(anchor) = _frame.info().web_info.location.hash.strip_prefix('#') {
-            self.menu_options.anchor = match ChapterAnchor::read_str(anchor) {
-                Some(a) => a,
-                None => ChapterAnchor::Index,
-            };
-        }
+        self.follow_link(&_frame.info().web_info.location.hash);
+        // after following the link, such that the url keeps the query of the link
+        let old_anchor = self.menu_options.anchor;
         self.text = format!("{:?}", self.menu_options.anchor);
 
         super::default_menu(ctx, &mut self.menu_options);
 
+        if self.menu_options.copy_link {
+            self.menu_options.copy_link = false;
+            let link = self.link();
+            // the url also contains the current hash, which is replaced by the link
+            #[cfg(target_arch = "wasm32")]
+            let link = {
+                let url = &_frame.info().web_info.location.url;
+                let base = url.split_once('#').map_or(url.as_str(), |(base, _)| base);
+                format!("{base}{link}")
+            };
+            ctx.copy_text(link);
+        }
+        self.theme = ctx.options(|options| options.theme_preference);
+
+        if self.menu_options.reset_page {
+            self.pages.reset(&self.menu_options.anchor);
+            self.menu_options.reset_page = false;
+        }
+
         if old_anchor == self.menu_options.anchor {
             // like this I can now get default values or the stored value,
             // so I can use this to switch between them
+            let anything = self.pages.get_mut(self.menu_options.anchor);
             match &self.menu_options.anchor {
                 ChapterAnchor::Chapter1(which) => {
-                    chapter1::chapter_1_switch(which, &mut self.anything, ctx);
+                    chapter1::chapter_1_switch(which, anything, ctx);
                 }
-                ChapterAnchor::Chapter2(_) => {
-                    let some: &mut u32 = self.anything.to_something_or_default_mut();
-                    *some = 10;
+                ChapterAnchor::Chapter2(which) => {
+                    chapter2::chapter_2_switch(which, anything, ctx);
                 }
                 ChapterAnchor::Index => {
                     index_page(ctx, &mut self.menu_options.anchor);
@@ -1,6 +1,7 @@
 use super::chapter_markers::*;
 use crate::misc::*;
 use egui::{Color32, Label, Sense, Window};
+use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
 
 pub trait MenuAction {
@@ -163,10 +164,19 @@ impl MenuOrSubMenu {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct MenuOptions {
     font_popup: bool,
     pub anchor: ChapterAnchor,
+    /// The user wants to throw away the state of the current page
+    #[serde(skip)]
+    pub reset_page: bool,
+    /// The user wants a link to the current page, including the parameters of the demo
+    #[serde(skip)]
+    pub copy_link: bool,
+    /// Only if the user opts in, the settings are stored in the browser or in a file
+    #[serde(skip)]
+    pub remember: bool,
 }
 
 pub fn default_menu(ctx: &egui::Context, opt: &mut MenuOptions) {
@@ -189,6 +199,20 @@ pub fn default_menu(ctx: &egui::Context, opt: &mut MenuOptions) {
                 opt.font_popup = !opt.font_popup;
             }
 
+            ui.checkbox(&mut opt.remember, "Remember settings")
+                .on_hover_text("Store the current page, the theme and the parameters of the simulations, such that you can continue where you left off");
+
+            if opt.anchor != ChapterAnchor::Index {
+                opt.copy_link = ui
+                    .button("Copy link")
+                    .on_hover_text("Copy a link to this page that also contains the parameters of the simulation")
+                    .clicked();
+                opt.reset_page = ui
+                    .button("Reset page")
+                    .on_hover_text("Start this page from scratch. Other pages keep their state")
+                    .clicked();
+            }
+
             Window::new("exp")
                 .fixed_pos([50., 50.])
                 .resizable(false)
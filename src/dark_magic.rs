@@ -1,4 +1,10 @@
 mod boxed_anything;
 pub use boxed_anything::*;
+mod file_io;
+pub use file_io::*;
+mod keyed_anything;
+pub use keyed_anything::*;
+mod scheduler;
+pub use scheduler::*;
 mod thread_helper;
 pub use thread_helper::*;
@@ -0,0 +1,70 @@
+/*
+    File dialogs that work natively and in the browser.
+
+    In the browser everything regarding files is asynchronous,
+    so the dialogs are always started in the background and
+    the GUI has to poll the result.
+    Natively the dialog runs in its own thread, such that the GUI is not blocked.
+*/
+
+use rfd::AsyncFileDialog;
+use std::{
+    future::Future,
+    sync::mpsc::{channel, Receiver},
+};
+
+/// # Result of a file dialog that is not finished yet
+#[derive(Debug)]
+pub struct PendingFile<T> {
+    receiver: Receiver<T>,
+}
+
+impl<T> PendingFile<T> {
+    /// None while the user is still choosing the file
+    pub fn poll(&self) -> Option<T> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// Lets the user choose a file and reads it.
+///
+/// The result is None if the user cancelled the dialog
+pub fn pick_file(filter_name: &str, extensions: &[&str]) -> PendingFile<Option<Vec<u8>>> {
+    let (sender, receiver) = channel();
+    let dialog = AsyncFileDialog::new().add_filter(filter_name, extensions);
+    spawn(async move {
+        let contents = match dialog.pick_file().await {
+            Some(handle) => Some(handle.read().await),
+            None => None,
+        };
+        let _ = sender.send(contents);
+    });
+    PendingFile { receiver }
+}
+
+/// Lets the user choose where to save `contents`.
+/// In the browser this is a download.
+///
+/// The result is None if the user cancelled the dialog
+pub fn save_file(file_name: &str, contents: Vec<u8>) -> PendingFile<Option<std::io::Result<()>>> {
+    let (sender, receiver) = channel();
+    let dialog = AsyncFileDialog::new().set_file_name(file_name);
+    spawn(async move {
+        let result = match dialog.save_file().await {
+            Some(handle) => Some(handle.write(&contents).await),
+            None => None,
+        };
+        let _ = sender.send(result);
+    });
+    PendingFile { receiver }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn spawn<F: Future<Output = ()> + 'static>(future: F) {
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn<F: Future<Output = ()> + Send + 'static>(future: F) {
+    std::thread::spawn(move || pollster::block_on(future));
+}
@@ -0,0 +1,40 @@
+use super::BoxedAnything;
+use std::{collections::HashMap, hash::Hash};
+
+/// # One [BoxedAnything] per key
+///
+/// Used to give every page its own state, such that
+/// a long running simulation survives visiting another page
+pub struct KeyedAnything<K> {
+    content: HashMap<K, BoxedAnything>,
+}
+
+impl<K> Default for KeyedAnything<K> {
+    fn default() -> Self {
+        Self {
+            content: HashMap::new(),
+        }
+    }
+}
+
+impl<K> KeyedAnything<K>
+where
+    K: Eq + Hash,
+{
+    /// The state belonging to `key`. Empty if it was never used or reset
+    pub fn get_mut(&mut self, key: K) -> &mut BoxedAnything {
+        self.content
+            .entry(key)
+            .or_insert_with(|| BoxedAnything::new(()))
+    }
+
+    /// The state belonging to `key`, if it was ever used
+    pub fn get(&self, key: &K) -> Option<&BoxedAnything> {
+        self.content.get(key)
+    }
+
+    /// Drops the state belonging to `key`, so the next access starts from the default
+    pub fn reset(&mut self, key: &K) {
+        self.content.remove(key);
+    }
+}
@@ -0,0 +1,132 @@
+use derivative::Derivative;
+use std::{num::NonZeroUsize, time::Duration};
+use web_time::Instant;
+
+/// The budget is adapted such that the app keeps running at 60 frames per second
+const TARGET_FRAME_TIME: Duration = Duration::from_micros(16_667);
+const INITIAL_BUDGET: Duration = Duration::from_millis(5);
+const MIN_BUDGET: Duration = Duration::from_millis(1);
+const MAX_BUDGET: Duration = Duration::from_millis(12);
+/// Time budget per frame in slow motion
+const SLOW_MOTION_BUDGET: Duration = Duration::from_micros(100);
+/// Longer gaps between two frames mean that no repaint was requested in between,
+/// so they say nothing about how long a frame takes
+const MAX_MEASURED_FRAME_TIME: Duration = Duration::from_millis(250);
+/// Weight of the newest frame in the displayed steps per second
+const SMOOTHING: f64 = 0.1;
+
+/// A simulation that can be advanced in small time slices from the UI thread
+pub trait TimeSliced {
+    /// Advances the simulation until `deadline` has passed.
+    ///
+    /// Returns the number of performed steps
+    fn step_budget(&mut self, deadline: Instant) -> u64;
+
+    /// Like [`TimeSliced::step_budget`], but performs at most `max_steps` steps.
+    /// This is used for slow motion, so simulations without single steps worth
+    /// watching can ignore the limit
+    fn step_budget_limited(&mut self, deadline: Instant, max_steps: NonZeroUsize) -> u64 {
+        let _ = max_steps;
+        self.step_budget(deadline)
+    }
+}
+
+/// # Shares the time of every frame between the simulations of a page
+///
+/// The budget grows as long as the frames are fast enough and shrinks otherwise.
+/// While the tab is hidden or the window is minimized nothing runs at all
+#[derive(Debug, Derivative)]
+#[derivative(Default)]
+pub struct Scheduler {
+    #[derivative(Default(value = "INITIAL_BUDGET"))]
+    budget: Duration,
+    last_frame: Option<Instant>,
+    /// One entry per task of the last [`Scheduler::run`]
+    steps_per_second: Vec<f64>,
+    hidden: bool,
+}
+
+impl Scheduler {
+    /// Has to be called once per frame. Every task gets an equal share of the budget.
+    ///
+    /// With `max_steps` the tasks run in slow motion, i.e., with a small fixed budget
+    /// and at most `max_steps` steps each
+    pub fn run(
+        &mut self,
+        ctx: &egui::Context,
+        max_steps: Option<NonZeroUsize>,
+        tasks: &mut [&mut dyn TimeSliced],
+    ) {
+        let now = Instant::now();
+        self.hidden = is_hidden(ctx);
+        if self.hidden {
+            // the gap until the page is visible again should not count as a frame
+            self.last_frame = None;
+            return;
+        }
+        let frame_time = self
+            .last_frame
+            .replace(now)
+            .map(|last| now.duration_since(last))
+            .filter(|frame_time| *frame_time < MAX_MEASURED_FRAME_TIME);
+
+        let budget = match (max_steps, frame_time) {
+            (Some(_), _) => SLOW_MOTION_BUDGET,
+            (None, Some(frame_time)) => {
+                self.adapt_budget(frame_time);
+                self.budget
+            }
+            (None, None) => self.budget,
+        };
+
+        self.steps_per_second.resize(tasks.len(), 0.0);
+        let share = budget / tasks.len().max(1) as u32;
+        for (task, rate) in tasks.iter_mut().zip(self.steps_per_second.iter_mut()) {
+            let deadline = Instant::now() + share;
+            let steps = match max_steps {
+                Some(max_steps) => task.step_budget_limited(deadline, max_steps),
+                None => task.step_budget(deadline),
+            };
+            if let Some(frame_time) = frame_time {
+                let current = steps as f64 / frame_time.as_secs_f64();
+                *rate += SMOOTHING * (current - *rate);
+            }
+        }
+    }
+
+    fn adapt_budget(&mut self, frame_time: Duration) {
+        let factor = if frame_time > TARGET_FRAME_TIME.mul_f64(1.1) {
+            0.9
+        } else {
+            1.05
+        };
+        self.budget = self.budget.mul_f64(factor).clamp(MIN_BUDGET, MAX_BUDGET);
+    }
+
+    /// Time the tasks get per frame if they do not run in slow motion
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Was the page hidden during the last [`Scheduler::run`]?
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// Smoothed steps per second of the task with index `task` in the last [`Scheduler::run`]
+    pub fn steps_per_second(&self, task: usize) -> f64 {
+        self.steps_per_second.get(task).copied().unwrap_or(0.0)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn is_hidden(_: &egui::Context) -> bool {
+    web_sys::window()
+        .and_then(|window| window.document())
+        .is_some_and(|document| document.hidden())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn is_hidden(ctx: &egui::Context) -> bool {
+    ctx.input(|i| i.viewport().minimized == Some(true))
+}
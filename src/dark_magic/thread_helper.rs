@@ -1,27 +1,23 @@
 /*
-    ATTENTION!
-
-    Currently this does not work in WebAssembly, only natively.
-    Since I primarily want to run this in wasm later,
-    this needs further debugging.
-
-    Currently the chapter1->second is trying to use this and
-    crashing instantly in the webapp (natively it runs fine).
-
-    I suspect that either RwLock or Mutex or Arc or thread spawning is the issue.
-
-    I should start by researching if wasm even supports multiple threads, this might be the core of the problem.
-    If that is the case, I can probably delete this whole file as then I need to do everything in the UI thread
-    anyways.
-
-    So to debug later I should try to use those one after another and see when my app is crashing.
+    wasm32-unknown-unknown has no threads unless it is compiled with shared memory,
+    which also needs special headers from the web server. Spawning a thread in the
+    webapp therefore panics instantly.
 
+    ThreadHelper itself is only a pair of shared locks and works everywhere.
+    BackgroundComputation builds on it and either drives the computation on a
+    std::thread (native) or in small time slices from the UI thread (wasm),
+    so the pages do not need to care which one is used.
+    The parallel replicas of chapter1->parallel tempering run this way.
 */
 
+use std::any::Any;
 use std::ops::{Deref, DerefMut};
-use std::sync::{Arc, Mutex, MutexGuard, RwLockWriteGuard};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, RwLockWriteGuard};
+use std::time::Duration;
 
 use std::sync::RwLock;
+use web_time::Instant;
 
 #[derive(Default, Debug)]
 pub enum ExposedData<T> {
@@ -135,3 +131,378 @@ impl<T, E> ThreadHelper<T, E> {
         }
     }
 }
+
+/// A long computation that can be split into small chunks of work
+pub trait Work: Send + 'static {
+    /// What the UI gets to see of the computation
+    type Snapshot: Send + Sync + 'static;
+
+    /// Performs a small chunk of work, ideally well below a millisecond.
+    ///
+    /// Returns false once there is nothing left to do
+    fn work_chunk(&mut self) -> bool;
+
+    /// Current state of the computation, for drawing stuff or whatever
+    fn snapshot(&self) -> Self::Snapshot;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Computes on its own thread. Not available in wasm,
+    /// where this falls back to [`Backend::TimeSliced`]
+    Thread,
+    /// Computes on the UI thread, but only during [`BackgroundComputation::tick`]
+    TimeSliced,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        if cfg!(target_arch = "wasm32") {
+            Backend::TimeSliced
+        } else {
+            Backend::Thread
+        }
+    }
+}
+
+/// Where a [`BackgroundComputation`] is at
+#[derive(Debug)]
+enum Status {
+    Working,
+    /// Nothing left to do, until more work is fed
+    Finished,
+    /// The work panicked, the payload is handed out once by [`BackgroundComputation::wait`]
+    Panicked(Option<Box<dyn Any + Send>>),
+    /// The main copy was dropped
+    Stopping,
+}
+
+/// Status that the main thread and the worker thread can wait for
+#[derive(Debug)]
+struct Signal {
+    status: Mutex<Status>,
+    changed: Condvar,
+}
+
+impl Signal {
+    fn lock(&self) -> MutexGuard<'_, Status> {
+        self.status.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn set(&self, status: Status) {
+        *self.lock() = status;
+        self.changed.notify_all();
+    }
+}
+
+/// Runs some [`Work`] in the background and exposes snapshots of it.
+///
+/// Once there is nothing left to do, the thread waits until more work is fed,
+/// so the same thread can be reused again and again.
+/// Dropping it stops the computation
+#[derive(Debug)]
+pub struct BackgroundComputation<W: Work> {
+    helper: ThreadHelper<W, W::Snapshot>,
+    backend: Backend,
+    signal: Arc<Signal>,
+}
+
+impl<W: Work> BackgroundComputation<W> {
+    pub fn new(work: W, backend: Backend) -> Self {
+        let backend = if cfg!(target_arch = "wasm32") {
+            Backend::TimeSliced
+        } else {
+            backend
+        };
+        let helper = ThreadHelper {
+            internal_data: Arc::new(Mutex::new(work)),
+            exposed_data: Arc::new(RwLock::new(ExposedData::NotExisting)),
+        };
+        let signal = Arc::new(Signal {
+            status: Mutex::new(Status::Working),
+            changed: Condvar::new(),
+        });
+        if backend == Backend::Thread {
+            let worker = helper
+                .get_clone()
+                .expect("freshly created helper has no other copies");
+            let worker_signal = signal.clone();
+            std::thread::spawn(move || Self::thread_loop(worker, worker_signal));
+        }
+        Self {
+            helper,
+            backend,
+            signal,
+        }
+    }
+
+    fn thread_loop(worker: ThreadHelper<W, W::Snapshot>, signal: Arc<Signal>) {
+        // the locks fail once the main copy is gone, which ends the thread
+        while let Some(mut work) = worker.internal_data_lock() {
+            // the lock is held outside of the unwinding, so the work can still be accessed
+            let more = match catch_unwind(AssertUnwindSafe(|| work.work_chunk())) {
+                Ok(more) => more,
+                Err(panic) => {
+                    drop(work);
+                    signal.set(Status::Panicked(Some(panic)));
+                    return;
+                }
+            };
+            let snapshot = work.snapshot();
+            drop(work);
+            match worker.exposed_data_write_lock() {
+                Some(mut exposed) => *exposed = ExposedData::Exists(snapshot),
+                None => return,
+            }
+            let mut status = signal.lock();
+            if !more && matches!(*status, Status::Working) {
+                *status = Status::Finished;
+                signal.changed.notify_all();
+            }
+            while matches!(*status, Status::Finished) {
+                status = signal
+                    .changed
+                    .wait(status)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+            if matches!(*status, Status::Stopping) {
+                return;
+            }
+        }
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(*self.signal.lock(), Status::Finished)
+    }
+
+    /// Has to be called every frame. For [`Backend::TimeSliced`] this works on
+    /// the computation until `budget` has passed, otherwise it does nothing
+    pub fn tick(&self, budget: Duration) {
+        if self.backend != Backend::TimeSliced || self.is_finished() {
+            return;
+        }
+        let start = Instant::now();
+        let Ok(mut work) = self.helper.internal_data.lock() else {
+            return;
+        };
+        let mut more = true;
+        while more && start.elapsed() < budget {
+            more = work.work_chunk();
+        }
+        let snapshot = work.snapshot();
+        drop(work);
+        if let Ok(mut exposed) = self.helper.exposed_data.write() {
+            *exposed = ExposedData::Exists(snapshot);
+        }
+        if !more {
+            self.signal.set(Status::Finished);
+        }
+    }
+
+    /// A copy of the latest snapshot
+    pub fn snapshot(&self) -> Option<W::Snapshot>
+    where
+        W::Snapshot: Clone,
+    {
+        self.helper.exposed_data_deep_clone()
+    }
+
+    /// Takes the latest snapshot, if there is a new one
+    pub fn take_snapshot(&self) -> Option<W::Snapshot> {
+        self.helper.exposed_data.write().ok()?.take()
+    }
+
+    /// Direct access to the work. Blocks while the worker thread performs a chunk,
+    /// so this is meant for the time after [`BackgroundComputation::wait`]
+    pub fn access<R>(&self, f: impl FnOnce(&mut W) -> R) -> R {
+        let mut work = self
+            .helper
+            .internal_data
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        f(&mut work)
+    }
+
+    /// Changes the work via `f` and continues the computation, also if it was finished
+    pub fn feed(&self, f: impl FnOnce(&mut W)) {
+        self.access(f);
+        let mut status = self.signal.lock();
+        if matches!(*status, Status::Finished) {
+            *status = Status::Working;
+            self.signal.changed.notify_all();
+        }
+    }
+
+    /// Blocks until there is nothing left to do.
+    /// For [`Backend::TimeSliced`] the remaining work is done right here.
+    ///
+    /// Returns the payload if the work panicked. The computation is over then,
+    /// but the work can still be accessed
+    pub fn wait(&self) -> Result<(), Box<dyn Any + Send>> {
+        while self.backend == Backend::TimeSliced && !self.is_finished() {
+            self.tick(Duration::MAX);
+        }
+        let mut status = self.signal.lock();
+        while matches!(*status, Status::Working) {
+            status = self
+                .signal
+                .changed
+                .wait(status)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        match &mut *status {
+            Status::Panicked(panic) => Err(panic
+                .take()
+                .unwrap_or_else(|| Box::new("the background computation panicked before"))),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<W: Work> Drop for BackgroundComputation<W> {
+    fn drop(&mut self) {
+        let mut status = self.signal.lock();
+        if !matches!(*status, Status::Panicked(_)) {
+            *status = Status::Stopping;
+        }
+        self.signal.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_pcg::Pcg64;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Random walk that tells the test when it is dropped
+    struct RandomWalk {
+        rng: Pcg64,
+        position: i64,
+        steps_left: u64,
+        dropped: Arc<AtomicBool>,
+    }
+
+    impl RandomWalk {
+        fn new(steps: u64, dropped: Arc<AtomicBool>) -> Self {
+            Self {
+                rng: Pcg64::seed_from_u64(12),
+                position: 0,
+                steps_left: steps,
+                dropped,
+            }
+        }
+    }
+
+    impl Drop for RandomWalk {
+        fn drop(&mut self) {
+            self.dropped.store(true, Ordering::Release);
+        }
+    }
+
+    impl Work for RandomWalk {
+        type Snapshot = (i64, u64);
+
+        fn work_chunk(&mut self) -> bool {
+            for _ in 0..1000.min(self.steps_left) {
+                self.position += if self.rng.random() { 1 } else { -1 };
+                self.steps_left -= 1;
+            }
+            self.steps_left > 0
+        }
+
+        fn snapshot(&self) -> Self::Snapshot {
+            (self.position, self.steps_left)
+        }
+    }
+
+    fn wait_until(condition: impl Fn() -> bool) {
+        let start = Instant::now();
+        while !condition() {
+            assert!(start.elapsed() < Duration::from_secs(60), "timed out");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn backends_agree() {
+        let results: Vec<_> = [Backend::Thread, Backend::TimeSliced]
+            .into_iter()
+            .map(|backend| {
+                let dropped = Arc::new(AtomicBool::new(false));
+                let computation =
+                    BackgroundComputation::new(RandomWalk::new(5_000_000, dropped), backend);
+                wait_until(|| {
+                    computation.tick(Duration::from_millis(16));
+                    computation.is_finished()
+                });
+                computation.snapshot().unwrap()
+            })
+            .collect();
+        assert_eq!(results[0].1, 0);
+        assert_eq!(results[0], results[1]);
+    }
+
+    #[test]
+    fn feeding_continues_finished_work() {
+        for backend in [Backend::Thread, Backend::TimeSliced] {
+            let dropped = Arc::new(AtomicBool::new(false));
+            let computation =
+                BackgroundComputation::new(RandomWalk::new(100_000, dropped.clone()), backend);
+            for _ in 0..3 {
+                computation.wait().unwrap();
+                assert!(computation.is_finished());
+                assert_eq!(computation.access(|walk| walk.steps_left), 0);
+                computation.feed(|walk| walk.steps_left = 100_000);
+            }
+            computation.wait().unwrap();
+            assert_eq!(computation.access(|walk| walk.steps_left), 0);
+            assert!(!dropped.load(Ordering::Acquire));
+        }
+    }
+
+    struct Panicking {
+        chunks: u32,
+    }
+
+    impl Work for Panicking {
+        type Snapshot = u32;
+
+        fn work_chunk(&mut self) -> bool {
+            self.chunks += 1;
+            if self.chunks == 3 {
+                panic!("third chunk");
+            }
+            true
+        }
+
+        fn snapshot(&self) -> Self::Snapshot {
+            self.chunks
+        }
+    }
+
+    #[test]
+    fn panic_keeps_work() {
+        let computation = BackgroundComputation::new(Panicking { chunks: 0 }, Backend::Thread);
+        let panic = computation.wait().unwrap_err();
+        assert_eq!(panic.downcast_ref::<&str>(), Some(&"third chunk"));
+        assert_eq!(computation.access(|work| work.chunks), 3);
+        assert!(computation.wait().is_err());
+    }
+
+    #[test]
+    fn dropping_stops_thread() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let computation =
+            BackgroundComputation::new(RandomWalk::new(u64::MAX, dropped.clone()), Backend::Thread);
+        wait_until(|| computation.take_snapshot().is_some());
+        drop(computation);
+        // the worker thread owns the last copy of the walk, so it is dropped once the thread ends
+        wait_until(|| dropped.load(Ordering::Acquire));
+    }
+}
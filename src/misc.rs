@@ -1,6 +1,7 @@
 use std::{collections::VecDeque, num::NonZeroUsize};
 
 use egui::{Color32, RichText};
+use serde::{Deserialize, Serialize};
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const GIT_HASH: &str = env!("GIT_HASH");
@@ -27,7 +28,7 @@ impl DarkLightColor {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RingBuffer<T> {
     buffer: VecDeque<T>,
     max_len: NonZeroUsize,
@@ -72,6 +73,11 @@ impl<T> RingBuffer<T> {
     pub fn len(&self) -> usize {
         self.buffer.len()
     }
+
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.buffer.len() == self.max_len.get()
+    }
 }
 
 pub const HINT: &str = "To increase the size of the texts you can press 'ctrl' + '+'